@@ -1,30 +1,57 @@
-use rapier2d::{dynamics::RigidBodyType, geometry::CollisionEvent};
+use rapier2d::math::Vector;
 use sfml::{
-    graphics::{Color, RenderStates, RenderTarget, RenderWindow},
-    window::{Event, Key},
+    graphics::RenderWindow,
+    system::Clock,
+    window::{mouse::Button, Event, Key},
+    SfBox,
 };
 
 use crate::{
+    assets::{AssetError, Assets},
     ball::{Ball, BallSize},
-    physics::{Physics, PhysicsObject},
-    ring::{Ring, RingSize},
-    sounds::{SoundList, SoundType, Sounds},
-    util::Drawable,
+    melody::Melody,
+    render::sfml::SfmlRenderer,
+    scene::Scene,
+    sim::{RunReport, Simulation},
+    sounds::{SoundList, Sounds},
 };
 
+/// The interactive front end: shows a [`Simulation`] in a window and plays
+/// its sounds live.
 pub struct App<'s> {
+    title: String,
     window: RenderWindow,
-    physics: Physics,
-    balls: Vec<Ball<'s>>,
-    rings: Vec<Ring<'s>>,
+    sim: Simulation,
     soundlist: SoundList<'s>,
     sounds: Sounds<'s>,
+    max_substeps: u32,
+    auto_exit: bool,
 }
 
+/// Wall-clock seconds the final screen stays up before the window closes by
+/// itself.
+const FINAL_SCREEN_SECONDS: f32 = 3.0;
+
 impl<'s> App<'s> {
-    pub fn new(title: &str) -> Self {
+    /// Opens the window for `scene`, playing sounds from `assets`. Fails if
+    /// a sound can't be loaded.
+    pub fn new(
+        title: &str,
+        scene: &Scene,
+        seed: u64,
+        assets: &'s Assets,
+    ) -> Result<Self, AssetError> {
+        let sim = Simulation::new(scene, seed);
+        let mut soundlist = SoundList::new(assets);
+        soundlist.set_voices(scene.voices);
+        soundlist.preload(&sim.sampled_sounds())?;
+        soundlist.load_bank(sim.sound_bank())?;
+        soundlist.prerender(&sim.voice_pitches());
+        let mut sounds = Sounds::new();
+        sounds.set_polyphony(sim.polyphony().clone());
+
         let mut window = RenderWindow::new(
-            (640, 360),
+            (scene.window.width, scene.window.height),
             title,
             sfml::window::Style::CLOSE,
             &sfml::window::ContextSettings {
@@ -33,161 +60,136 @@ impl<'s> App<'s> {
             },
         );
         window.set_vertical_sync_enabled(true);
-        let mut physics = Physics::new();
-
-        let mut balls = vec![
-            Ball::new_with_size((290.0, 180.0), BallSize::Small),
-            Ball::new_with_size((350.0, 180.0), BallSize::Small),
-        ];
 
-        balls.iter_mut().enumerate().for_each(|(_i, ball)| {
-            ball.insert_into_physics(RigidBodyType::Dynamic, &mut physics);
-        });
+        Ok(Self {
+            title: title.to_owned(),
+            window,
+            sim,
+            soundlist,
+            sounds,
+            max_substeps: 5,
+            auto_exit: false,
+        })
+    }
 
-        let mut rings = vec![Ring::new_with_size((320.0, 180.0), RingSize::Large)];
+    /// Caps how many physics steps a single displayed frame may catch up on,
+    /// so a slow frame makes the simulation fall behind instead of spiralling.
+    pub fn set_max_substeps(&mut self, max_substeps: u32) {
+        self.max_substeps = max_substeps.max(1);
+    }
 
-        rings.iter_mut().for_each(|ring| {
-            ring.insert_into_physics(RigidBodyType::Fixed, &mut physics);
-        });
+    pub fn set_melody(&mut self, melody: Option<Melody>) {
+        self.sim.set_melody(melody);
+        self.soundlist.prerender(&self.sim.voice_pitches());
+    }
 
-        let mut soundlist = SoundList::new();
-        soundlist.preload();
-        let sounds = Sounds::new();
+    /// Closes the window a few seconds after the run ends instead of waiting
+    /// for Q.
+    pub fn set_auto_exit(&mut self, auto_exit: bool) {
+        self.auto_exit = auto_exit;
+    }
 
-        Self {
-            window,
-            physics,
-            balls,
-            rings,
-            soundlist,
-            sounds,
-        }
+    pub fn report(&self) -> RunReport {
+        self.sim.report()
     }
 
+    /// Shows the simulation until the window is closed. Once the run ends it
+    /// stops stepping and keeps the final screen up, with the outcome in the
+    /// title bar.
+    ///
+    /// Until then the mouse plays along: left-click spawns a ball, or grabs
+    /// the one under the pointer to drag and fling it, and right-click
+    /// deletes one.
     pub fn run(&mut self) {
+        let timestep = self.sim.timestep();
+        let mut accumulator = 0.0;
+        let mut clock = Clock::start();
+        let mut final_screen: Option<SfBox<Clock>> = None;
         while self.window.is_open() {
             while let Some(event) = self.window.poll_event() {
                 match event {
                     Event::Closed => self.window.close(),
-                    Event::KeyPressed { code, .. } => {
-                        if code == Key::Q {
-                            self.window.close();
-                        }
-                    }
+                    Event::KeyPressed { code: Key::Q, .. } => self.window.close(),
+                    _ if final_screen.is_some() => {}
+                    Event::MouseButtonPressed { button, x, y } => self.press(button, pointer(x, y)),
+                    Event::MouseMoved { x, y } => self.sim.drag_to(pointer(x, y)),
+                    Event::MouseButtonReleased {
+                        button: Button::Left,
+                        ..
+                    } => self.sim.release_ball(),
                     _ => {}
                 }
             }
-            self.window.clear(Color::BLACK);
 
-            self.update();
-            self.draw();
+            if let Some(shown) = &final_screen {
+                if self.auto_exit && shown.elapsed_time().as_seconds() >= FINAL_SCREEN_SECONDS {
+                    self.window.close();
+                }
+                self.sounds.update();
+                self.sim.draw(&mut SfmlRenderer::new(&mut self.window), 1.0);
+                self.window.display();
+                continue;
+            }
 
-            self.window.display();
-        }
-    }
+            accumulator += clock.restart().as_seconds();
+            let mut substeps = 0;
+            while accumulator >= timestep
+                && substeps < self.max_substeps
+                && self.sim.ending().is_none()
+            {
+                self.update();
+                accumulator -= timestep;
+                substeps += 1;
+            }
+            if substeps == self.max_substeps {
+                accumulator %= timestep;
+            }
+            if let Some(ending) = self.sim.ending() {
+                self.window.set_title(&format!("{} - {ending}", self.title));
+                final_screen = Some(Clock::start());
+            }
 
-    fn update(&mut self) {
-        for ball in &mut self.balls {
-            ball.update(&mut self.physics);
+            self.sim.draw(
+                &mut SfmlRenderer::new(&mut self.window),
+                accumulator / timestep,
+            );
+            self.window.display();
         }
-        self.physics.step();
-        self.physics
-            .get_collision_events()
-            .iter()
-            .for_each(|event| {
-                if event.stopped() && !event.removed() {
-                    self.react_to_collision(*event);
-                }
-            });
-        self.sounds.update();
-        self.physics.cleanup();
     }
 
-    fn draw(&mut self) {
-        let states = RenderStates::default();
-
-        for ball in &mut self.balls {
-            ball.draw(&mut self.window, &states);
-        }
-        for ring in &mut self.rings {
-            ring.draw(&mut self.window, &states);
+    fn press(&mut self, button: Button, point: Vector<f32>) {
+        let under = self.sim.ball_at(point);
+        match (button, under) {
+            (Button::Left, Some(ball)) => self.sim.grab_ball(ball, point),
+            (Button::Left, None) => {
+                let mut ball = Ball::new_with_size(point, BallSize::Small);
+                ball.rand_outline_color(self.sim.rng());
+                self.sim.add_ball(ball);
+            }
+            (Button::Right, Some(ball)) => {
+                self.sim.remove_ball(ball);
+            }
+            _ => {}
         }
     }
 
-    fn react_to_collision(&mut self, event: CollisionEvent) {
-        if !self.physics.is_collider_removed(event.collider1())
-            && !self.physics.is_collider_removed(event.collider2())
-        {
-            let rb1_handle = self
-                .physics
-                .collider_set
-                .get(event.collider1())
-                .unwrap()
-                .parent()
-                .unwrap();
-            let rb1 = self.physics.rigidbody_set.get(rb1_handle).unwrap();
-            let rb2_handle = self
-                .physics
-                .collider_set
-                .get(event.collider2())
-                .unwrap()
-                .parent()
-                .unwrap();
-            let rb2 = self.physics.rigidbody_set.get(rb2_handle).unwrap();
-            let combined_velocity_magnitude = rb1.linvel().norm() + rb2.linvel().norm();
-            self.sounds.play(
-                self.soundlist.get(SoundType::Bounce),
-                pitch(combined_velocity_magnitude),
-            );
-            let mut found_obj1 = false;
-            let mut found_obj2 = false;
-            for ball in &mut self.balls {
-                if found_obj1 && found_obj2 {
-                    break;
-                }
-                if ball.is_obj_with_handle(rb1_handle) && !found_obj1 {
-                    ball.rand_outline_color();
-                    ball.set_radius(ball.radius() * 1.01);
-                    self.physics.replace_collider(
-                        rb1_handle,
-                        event.collider1(),
-                        ball.create_collider(),
-                    );
-                    found_obj1 = true;
-                } else if ball.is_obj_with_handle(rb2_handle) && !found_obj2 {
-                    ball.rand_outline_color();
-                    ball.set_radius(ball.radius() * 1.01);
-                    self.physics.replace_collider(
-                        rb2_handle,
-                        event.collider2(),
-                        ball.create_collider(),
-                    );
-                    found_obj2 = true;
-                }
-            }
-            for ring in &mut self.rings {
-                if found_obj1 && found_obj2 {
-                    break;
-                }
-                if ring.is_obj_with_handle(rb1_handle) && !found_obj1 {
-                    ring.rand_outline_color();
-                    found_obj1 = true;
-                } else if ring.is_obj_with_handle(rb2_handle) && !found_obj2 {
-                    ring.rand_outline_color();
-                    found_obj2 = true;
-                }
+    fn update(&mut self) {
+        self.sim.step();
+        self.sim.take_collisions();
+        self.sim.take_escapes();
+        self.sim.take_breaks();
+        self.sim.take_merges();
+        for trigger in self.sim.take_sound_triggers() {
+            if let Some((buffer, pitch)) = self.soundlist.get_pitched(trigger.sound, trigger.pitch)
+            {
+                self.sounds.play(buffer, pitch, &trigger);
             }
         }
+        self.sounds.update();
     }
 }
 
-fn pitch(magnitude: f32) -> f32 {
-    let magnitude = magnitude as u32;
-    match magnitude {
-        0..=200 => magnitude as f32 / 200.0,
-        201..=1000 => 1.0 + (magnitude - 200) as f32 / 800.0,
-        1001..=2000 => 2.0 + (magnitude - 1000) as f32 / 1000.0,
-        2001..=4000 => 3.0 + (magnitude - 2000) as f32 / 2000.0,
-        _ => 4.0,
-    }
+/// Window coordinates are world coordinates, as the view is never moved.
+fn pointer(x: i32, y: i32) -> Vector<f32> {
+    Vector::new(x as f32, y as f32)
 }
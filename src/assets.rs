@@ -0,0 +1,206 @@
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+};
+
+use elsa::{FrozenMap, FrozenVec};
+#[cfg(feature = "sfml")]
+use sfml::{audio::SoundBuffer, SfBox};
+
+/// Environment variable naming a directory searched for assets before the
+/// default ones.
+pub const ASSETS_ENV: &str = "COLLIDE_AND_SOUND_ASSETS";
+
+/// Directories the bundled assets are looked up in, in order.
+#[derive(Clone, Debug)]
+pub struct AssetPaths {
+    dirs: Vec<PathBuf>,
+}
+
+impl Default for AssetPaths {
+    /// The directory in [`ASSETS_ENV`] if set, then `assets` next to the
+    /// executable, then `assets` in the working directory.
+    fn default() -> Self {
+        let mut dirs = Vec::new();
+        if let Some(dir) = env::var_os(ASSETS_ENV) {
+            dirs.push(PathBuf::from(dir));
+        }
+        if let Some(exe_dir) = env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_owned))
+        {
+            dirs.push(exe_dir.join("assets"));
+        }
+        dirs.push(PathBuf::from("assets"));
+        Self { dirs }
+    }
+}
+
+impl AssetPaths {
+    /// Searches only `dirs`, in order.
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        Self { dirs }
+    }
+
+    /// Searches `dir` before all the others.
+    pub fn prepend<P: Into<PathBuf>>(&mut self, dir: P) {
+        self.dirs.insert(0, dir.into());
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// The first existing file called `name` in the search directories.
+    pub fn find(&self, name: &str) -> Result<PathBuf, AssetError> {
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| AssetError::NotFound {
+                name: name.to_owned(),
+                searched: self.dirs.clone(),
+            })
+    }
+}
+
+/// Loaded assets of one kind. Each one stays at the same address for as
+/// long as the store lives, so loading only needs a shared reference and
+/// whatever borrows the assets, such as playing sounds, can keep doing so
+/// while more are loaded. They are freed together when the store is dropped,
+/// as playing sounds may borrow any of them until then.
+pub struct Store<T> {
+    files: FrozenMap<PathBuf, Box<T>>,
+    generated: FrozenVec<Box<T>>,
+}
+
+impl<T> Default for Store<T> {
+    fn default() -> Self {
+        Self {
+            files: FrozenMap::new(),
+            generated: FrozenVec::new(),
+        }
+    }
+}
+
+impl<T> Store<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The asset loaded from `path`, loading it with `load` the first time.
+    pub fn get_or_load(
+        &self,
+        path: &Path,
+        load: impl FnOnce(&Path) -> Result<T, AssetError>,
+    ) -> Result<&T, AssetError> {
+        if let Some(asset) = self.files.get(path) {
+            return Ok(asset);
+        }
+        let asset = load(path)?;
+        Ok(self.files.insert(path.to_owned(), Box::new(asset)))
+    }
+
+    /// Keeps an asset that was made rather than loaded, such as a
+    /// synthesized sound.
+    pub fn insert_generated(&self, asset: T) -> &T {
+        self.generated.push_get(Box::new(asset))
+    }
+
+    pub fn is_loaded(&self, path: &Path) -> bool {
+        self.files.get(path).is_some()
+    }
+}
+
+/// Every asset the interactive front end plays or shows, owned in one place
+/// and lent out for as long as it lives.
+#[cfg(feature = "sfml")]
+pub struct Assets {
+    paths: AssetPaths,
+    sounds: Store<SfBox<SoundBuffer>>,
+}
+
+#[cfg(feature = "sfml")]
+impl Assets {
+    pub fn new(paths: AssetPaths) -> Self {
+        Self {
+            paths,
+            sounds: Store::new(),
+        }
+    }
+
+    pub fn paths(&self) -> &AssetPaths {
+        &self.paths
+    }
+
+    /// The sound in the file at `path`, mixed down to mono so it can be
+    /// panned.
+    pub fn sound(&self, path: &Path) -> Result<&SfBox<SoundBuffer>, AssetError> {
+        self.sounds.get_or_load(path, load_mono)
+    }
+
+    /// A sound that was synthesized rather than loaded.
+    pub fn add_sound(&self, buffer: SfBox<SoundBuffer>) -> &SfBox<SoundBuffer> {
+        self.sounds.insert_generated(buffer)
+    }
+}
+
+#[cfg(feature = "sfml")]
+fn load_mono(path: &Path) -> Result<SfBox<SoundBuffer>, AssetError> {
+    let failed = |reason: &str| AssetError::Load {
+        path: path.to_owned(),
+        reason: reason.to_owned(),
+    };
+    if !path.is_file() {
+        return Err(failed("no such file"));
+    }
+    let filename = path
+        .to_str()
+        .ok_or_else(|| failed("path is not valid UTF-8"))?;
+    let buffer =
+        SoundBuffer::from_file(filename).map_err(|_| failed("not a supported sound file"))?;
+    let channels = buffer.channel_count() as usize;
+    if channels <= 1 {
+        return Ok(buffer);
+    }
+    let samples: Vec<i16> = buffer
+        .samples()
+        .chunks_exact(channels)
+        .map(|frame| (frame.iter().map(|&s| s as i32).sum::<i32>() / channels as i32) as i16)
+        .collect();
+    SoundBuffer::from_samples(&samples, 1, buffer.sample_rate())
+        .map_err(|_| failed("could not mix it down to mono"))
+}
+
+#[derive(Debug)]
+pub enum AssetError {
+    /// A bundled asset is in none of the search directories.
+    NotFound {
+        name: String,
+        searched: Vec<PathBuf>,
+    },
+    Load {
+        path: PathBuf,
+        reason: String,
+    },
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound { name, searched } => {
+                write!(f, "asset {name} not found in")?;
+                for (i, dir) in searched.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(f, "{separator}{}", dir.display())?;
+                }
+                Ok(())
+            }
+            Self::Load { path, reason } => {
+                write!(f, "failed to load {}: {reason}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
@@ -1,68 +1,90 @@
 #![allow(dead_code)]
 
+use std::f32::consts::PI;
+
 use rand::Rng;
 use rapier2d::prelude::*;
-use sfml::{
-    graphics::{CircleShape, Color, Drawable, RenderStates, RenderTarget, Shape, Transformable},
-    system::Vector2f,
-};
+use serde::Deserialize;
 
-use crate::util::ToNaMat2x1;
 use crate::{
-    physics::{Physics, PhysicsObject},
-    util,
+    physics::{Physics, PhysicsObject, DEFAULT_GRAVITY},
+    render::{Circle, Color, Drawable, Renderer},
 };
 
-pub struct Ball<'s> {
-    pub shape: CircleShape<'s>,
+/// How many times its own weight a contact force on a ball must be to be
+/// reported. Under gravity weaker than [`DEFAULT_GRAVITY`], or none at all,
+/// the weight it would have under that is used instead.
+const RESTING_FORCE_MARGIN: Real = 4.0;
+/// How long a ball that merged into another takes to shrink away, in
+/// seconds.
+const ABSORB_SECONDS: f32 = 0.25;
+
+pub struct Ball {
+    position: Vector<Real>,
+    radius: f32,
+    outline_thickness: f32,
+    outline_color: Color,
     rb_handle: Option<RigidBodyHandle>,
+    restitution: f32,
+    velocity: Vector<Real>,
+    previous_position: Vector<Real>,
+    absorbed: Option<Absorbed>,
 }
 
-impl Ball<'_> {
-    pub fn new<P: Into<Vector2f>>(pos: P) -> Self {
-        let mut shape = CircleShape::default();
-        shape.set_position(pos);
-        shape.set_radius(100.0);
-        shape.set_origin((100.0, 100.0));
-        shape.set_fill_color(sfml::graphics::Color::TRANSPARENT);
-        shape.set_outline_color(sfml::graphics::Color::WHITE);
-        shape.set_outline_thickness(5.0);
-        shape.set_point_count(100);
-        Self {
-            shape,
-            rb_handle: None,
-        }
+/// A ball that merged into another one, shrinking towards where that one is.
+#[derive(Clone, Copy)]
+struct Absorbed {
+    into: Vector<Real>,
+    previous_age: f32,
+    age: f32,
+}
+
+impl Ball {
+    pub fn new<P: Into<Vector<Real>>>(pos: P) -> Self {
+        Self::new_with_radius(pos, 100.0)
     }
 
-    pub fn new_with_size<P: Into<Vector2f>>(pos: P, size: BallSize) -> Self {
-        let mut shape = CircleShape::default();
+    pub fn new_with_size<P: Into<Vector<Real>>>(pos: P, size: BallSize) -> Self {
         let radius = match size {
             BallSize::Small => 15.0,
             BallSize::Medium => 100.0,
             BallSize::Large => 150.0,
         };
-        shape.set_position(pos);
-        shape.set_radius(radius);
-        shape.set_origin((radius, radius));
-        shape.set_fill_color(sfml::graphics::Color::TRANSPARENT);
-        shape.set_outline_color(sfml::graphics::Color::WHITE);
-        shape.set_outline_thickness(5.0);
-        shape.set_point_count(100);
+        Self::new_with_radius(pos, radius)
+    }
+
+    pub fn new_with_radius<P: Into<Vector<Real>>>(pos: P, radius: f32) -> Self {
+        let position = pos.into();
         Self {
-            shape,
+            position,
+            radius,
+            outline_thickness: 5.0,
+            outline_color: Color::WHITE,
             rb_handle: None,
+            restitution: 1.035,
+            velocity: Vector::zeros(),
+            previous_position: position,
+            absorbed: None,
         }
     }
 
     pub fn update(&mut self, physics: &mut Physics) {
+        if let Some(absorbed) = &mut self.absorbed {
+            absorbed.previous_age = absorbed.age;
+            absorbed.age += physics.timestep();
+        }
         if let Some(rbhandle) = self.rb_handle {
             if let Some(rb) = physics.rigidbody_set.get(rbhandle) {
-                let pos = rb.position().translation.vector;
-                self.shape.set_position((pos.x, pos.y));
+                self.previous_position = self.position;
+                self.position = rb.position().translation.vector;
             }
         }
     }
 
+    fn interpolated_position(&self, alpha: f32) -> Vector<Real> {
+        self.previous_position.lerp(&self.position, alpha)
+    }
+
     pub fn set_gravity_scale(&mut self, scale: f32, physics: &mut Physics) {
         if let Some(rbhandle) = self.rb_handle {
             if let Some(rb) = physics.rigidbody_set.get_mut(rbhandle) {
@@ -83,6 +105,10 @@ impl Ball<'_> {
         }
     }
 
+    pub fn rb_handle(&self) -> Option<RigidBodyHandle> {
+        self.rb_handle
+    }
+
     pub fn is_obj_with_handle(&self, handle: RigidBodyHandle) -> bool {
         if let Some(rbhandle) = self.rb_handle {
             rbhandle == handle
@@ -92,53 +118,138 @@ impl Ball<'_> {
     }
 
     pub fn set_outline_color(&mut self, color: Color) {
-        self.shape.set_outline_color(color);
+        self.outline_color = color;
     }
 
-    pub fn rand_outline_color(&mut self) {
-        let mut rng = rand::thread_rng();
-        self.shape.set_outline_color(Color::rgb(
+    pub fn rand_outline_color<R: Rng>(&mut self, rng: &mut R) {
+        self.set_outline_color(Color::rgb(
             rng.gen_range(10..255),
             rng.gen_range(10..255),
             rng.gen_range(10..255),
         ));
     }
 
+    pub fn set_outline_thickness(&mut self, thickness: f32) {
+        self.outline_thickness = thickness;
+    }
+
+    pub fn set_restitution(&mut self, restitution: f32) {
+        self.restitution = restitution;
+    }
+
+    /// Sets the linear velocity the ball starts with once inserted into
+    /// physics.
+    pub fn set_velocity<V: Into<Vector<Real>>>(&mut self, velocity: V) {
+        self.velocity = velocity.into();
+    }
+
     pub fn set_radius(&mut self, radius: f32) {
-        self.shape.set_radius(radius);
-        self.shape.set_origin((radius, radius));
+        self.radius = radius;
     }
 
     pub fn radius(&self) -> f32 {
-        self.shape.radius()
+        self.radius
+    }
+
+    pub fn position(&self) -> Vector<Real> {
+        self.position
+    }
+
+    pub fn outline_thickness(&self) -> f32 {
+        self.outline_thickness
+    }
+
+    pub fn outline_color(&self) -> Color {
+        self.outline_color
+    }
+
+    pub fn restitution(&self) -> f32 {
+        self.restitution
     }
 
-    pub fn create_collider(&mut self) -> Collider {
-        ColliderBuilder::ball(self.shape.radius() + self.shape.outline_thickness())
-            .active_events(ActiveEvents::COLLISION_EVENTS)
-            .restitution(1.035)
+    /// Takes the ball out of physics as it merges into one at `into`, and
+    /// lets it shrink away there. Returns the body it had, if it still had
+    /// one.
+    pub fn absorb_into(
+        &mut self,
+        into: Vector<Real>,
+        physics: &mut Physics,
+    ) -> Option<RigidBodyHandle> {
+        self.absorbed = Some(Absorbed {
+            into,
+            previous_age: 0.0,
+            age: 0.0,
+        });
+        let rb_handle = self.rb_handle.take()?;
+        physics.remove_body(rb_handle);
+        Some(rb_handle)
+    }
+
+    /// Takes the ball out of physics and lets it shrink away where it is.
+    /// Returns the body it had, if it still had one.
+    pub fn remove(&mut self, physics: &mut Physics) -> Option<RigidBodyHandle> {
+        self.absorb_into(self.position, physics)
+    }
+
+    /// Whether the ball merged into another or was removed, and is gone from
+    /// physics.
+    pub fn is_absorbed(&self) -> bool {
+        self.absorbed.is_some()
+    }
+
+    /// The ball's collider. Contact force events only fire for forces a few
+    /// times the ball's weight under the world's `gravity`, so resting on
+    /// something doesn't report one every step.
+    pub fn create_collider(&mut self, gravity: Vector<Real>) -> Collider {
+        let radius = self.radius + self.outline_thickness;
+        let weight = PI * radius * radius * gravity.norm().max(DEFAULT_GRAVITY);
+        ColliderBuilder::ball(radius)
+            .active_events(ActiveEvents::COLLISION_EVENTS | ActiveEvents::CONTACT_FORCE_EVENTS)
+            .contact_force_event_threshold(RESTING_FORCE_MARGIN * weight)
+            .restitution(self.restitution)
             .build()
     }
 }
 
-impl util::Drawable for Ball<'_> {
-    fn draw(&mut self, target: &mut dyn RenderTarget, states: &RenderStates) {
-        self.shape.draw(target, states);
+impl Drawable for Ball {
+    fn draw(&self, renderer: &mut dyn Renderer, alpha: f32) {
+        let (center, scale) = match self.absorbed {
+            Some(absorbed) => {
+                let age = absorbed.previous_age + (absorbed.age - absorbed.previous_age) * alpha;
+                if age >= ABSORB_SECONDS {
+                    return;
+                }
+                let t = age / ABSORB_SECONDS;
+                (self.position.lerp(&absorbed.into, t), 1.0 - t)
+            }
+            None => (self.interpolated_position(alpha), 1.0),
+        };
+        renderer.draw_circle(&Circle {
+            center,
+            radius: self.radius * scale,
+            outline_thickness: self.outline_thickness * scale,
+            fill: Color::TRANSPARENT,
+            outline: self.outline_color,
+            point_count: 100,
+        });
     }
 }
 
-impl PhysicsObject for Ball<'_> {
+impl PhysicsObject for Ball {
     fn insert_into_physics(&mut self, rbtype: RigidBodyType, physics: &mut Physics) {
         let rb = RigidBodyBuilder::new(rbtype)
             .ccd_enabled(true)
-            .translation(self.shape.position().to_na_mat2x1())
+            .translation(self.position)
+            .linvel(self.velocity)
             .build();
-        let collider = self.create_collider();
+        let collider = self.create_collider(physics.gravity());
         let rbhandle = physics.insert_body(rb, collider);
         self.rb_handle = Some(rbhandle);
     }
 }
 
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
 pub enum BallSize {
     Small,
     Medium,
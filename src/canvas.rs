@@ -0,0 +1,121 @@
+use std::{f32::consts::TAU, fs::File, io::BufWriter, path::Path};
+
+use crate::render::{Arc, Circle, Color, Renderer};
+
+/// An RGBA pixel buffer drawn into on the CPU, for rendering frames without
+/// an OpenGL context.
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    fn span(&self, from: f32, to: f32, limit: u32) -> std::ops::Range<u32> {
+        let start = from.floor().max(0.0) as u32;
+        let end = (to.ceil().max(0.0) as u32 + 1).min(limit);
+        start.min(end)..end
+    }
+
+    fn blend(&mut self, x: u32, y: u32, color: Color, coverage: f32) {
+        if coverage <= 0.0 {
+            return;
+        }
+        let alpha = color.a as f32 / 255.0 * coverage;
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        let pixel = &mut self.pixels[index..index + 4];
+        for (channel, source) in pixel.iter_mut().zip([color.r, color.g, color.b]) {
+            *channel = (source as f32 * alpha + *channel as f32 * (1.0 - alpha)).round() as u8;
+        }
+        pixel[3] = (255.0 * alpha + pixel[3] as f32 * (1.0 - alpha)).round() as u8;
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), png::EncodingError> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()
+    }
+}
+
+impl Renderer for Canvas {
+    fn clear(&mut self, color: Color) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+
+    /// Edges are antialiased by pixel coverage; `point_count` is ignored as
+    /// circles are rasterized exactly.
+    fn draw_circle(&mut self, circle: &Circle) {
+        let (radius, thickness) = (circle.radius, circle.outline_thickness);
+        let outer = radius + thickness.max(0.0);
+        let center = circle.center;
+        let x_range = self.span(center.x - outer, center.x + outer, self.width);
+        let y_range = self.span(center.y - outer, center.y + outer, self.height);
+
+        for y in y_range {
+            for x in x_range.clone() {
+                let dx = x as f32 + 0.5 - center.x;
+                let dy = y as f32 + 0.5 - center.y;
+                let distance = (dx * dx + dy * dy).sqrt();
+                let inside = coverage(radius - distance);
+                if circle.fill.a > 0 {
+                    self.blend(x, y, circle.fill, inside);
+                }
+                if thickness > 0.0 && circle.outline.a > 0 {
+                    let ring = coverage(outer - distance).min(1.0 - inside);
+                    self.blend(x, y, circle.outline, ring);
+                }
+            }
+        }
+    }
+
+    fn tint(&mut self, color: Color) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.blend(x, y, color, 1.0);
+            }
+        }
+    }
+
+    /// Antialiased along both the curved edges and the straight ends.
+    fn draw_arc(&mut self, arc: &Arc) {
+        let outer = arc.radius + arc.thickness;
+        let center = arc.center;
+        let x_range = self.span(center.x - outer, center.x + outer, self.width);
+        let y_range = self.span(center.y - outer, center.y + outer, self.height);
+        let half = arc.length / 2.0;
+        let middle = arc.start + half;
+
+        for y in y_range {
+            for x in x_range.clone() {
+                let dx = x as f32 + 0.5 - center.x;
+                let dy = y as f32 + 0.5 - center.y;
+                let distance = (dx * dx + dy * dy).sqrt();
+                let band = coverage(outer - distance).min(coverage(distance - arc.radius));
+                // How far along the circle the pixel is from the nearer end.
+                let from_middle = (dy.atan2(dx) - middle + TAU / 2.0).rem_euclid(TAU) - TAU / 2.0;
+                let ends = coverage((half - from_middle.abs()) * distance);
+                self.blend(x, y, arc.color, band.min(ends));
+            }
+        }
+    }
+}
+
+/// Fraction of a pixel covered by an edge `distance` pixels away from its
+/// center, positive towards the inside.
+fn coverage(distance: f32) -> f32 {
+    (distance + 0.5).clamp(0.0, 1.0)
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+
+use collide_and_sound::sim::RunLimit;
+
+pub struct Options {
+    pub headless: bool,
+    pub limit: Option<RunLimit>,
+    pub scene: Option<String>,
+    pub seed: Option<u64>,
+    pub physics_rate: Option<f32>,
+    pub max_substeps: Option<u32>,
+    pub wav: Option<String>,
+    pub record: Option<String>,
+    pub fps: f32,
+    pub assets: Option<String>,
+    /// Close the window once the final screen has been shown, and exit with
+    /// a status telling how the run ended.
+    pub auto_exit: bool,
+}
+
+#[derive(Debug)]
+pub enum OptionsError {
+    MissingValue(String),
+    InvalidValue { flag: String, value: String },
+    UnknownFlag(String),
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingValue(flag) => write!(f, "missing value for {flag}"),
+            Self::InvalidValue { flag, value } => write!(f, "invalid value for {flag}: {value}"),
+            Self::UnknownFlag(flag) => write!(f, "unknown option {flag}"),
+        }
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+impl Options {
+    pub fn from_env() -> Result<Self, OptionsError> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, OptionsError> {
+        let mut options = Self {
+            headless: false,
+            limit: None,
+            scene: None,
+            seed: None,
+            physics_rate: None,
+            max_substeps: None,
+            wav: None,
+            record: None,
+            fps: 60.0,
+            assets: None,
+            auto_exit: false,
+        };
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--headless" => options.headless = true,
+                "--auto-exit" => options.auto_exit = true,
+                "--ticks" => options.limit = Some(RunLimit::Ticks(value(&flag, &mut args)?)),
+                "--seconds" => {
+                    let seconds: f32 = value(&flag, &mut args)?;
+                    if seconds <= 0.0 || !seconds.is_finite() {
+                        return Err(OptionsError::InvalidValue {
+                            flag,
+                            value: seconds.to_string(),
+                        });
+                    }
+                    options.limit = Some(RunLimit::Seconds(seconds));
+                }
+                "--scene" => options.scene = Some(value(&flag, &mut args)?),
+                "--seed" => options.seed = Some(value(&flag, &mut args)?),
+                "--physics-rate" => {
+                    let rate: f32 = value(&flag, &mut args)?;
+                    if rate <= 0.0 || !rate.is_finite() {
+                        return Err(OptionsError::InvalidValue {
+                            flag,
+                            value: rate.to_string(),
+                        });
+                    }
+                    options.physics_rate = Some(rate);
+                }
+                "--max-substeps" => options.max_substeps = Some(value(&flag, &mut args)?),
+                "--wav" => options.wav = Some(value(&flag, &mut args)?),
+                "--record" => options.record = Some(value(&flag, &mut args)?),
+                "--assets" => options.assets = Some(value(&flag, &mut args)?),
+                "--fps" => {
+                    let fps: f32 = value(&flag, &mut args)?;
+                    if fps <= 0.0 || !fps.is_finite() {
+                        return Err(OptionsError::InvalidValue {
+                            flag,
+                            value: fps.to_string(),
+                        });
+                    }
+                    options.fps = fps;
+                }
+                _ => return Err(OptionsError::UnknownFlag(flag)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn value<T: std::str::FromStr>(
+    flag: &str,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<T, OptionsError> {
+    let value = args
+        .next()
+        .ok_or_else(|| OptionsError::MissingValue(flag.to_owned()))?;
+    value.parse().map_err(|_| OptionsError::InvalidValue {
+        flag: flag.to_owned(),
+        value,
+    })
+}
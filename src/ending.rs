@@ -0,0 +1,134 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+/// Whether a run that ended counts as won or lost.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    #[default]
+    Win,
+    Lose,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Outcome::Win => "win",
+            Outcome::Lose => "lose",
+        })
+    }
+}
+
+/// Something that ends the run when it happens, with the outcome it ends it
+/// with. Runs without any go on until stopped.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum EndCondition {
+    /// A ball inside a ring grows to `fraction` of the ring's radius,
+    /// counting its outline. A ball pressed against the ring all round stops
+    /// bouncing before it gets all the way, so the default leaves a margin.
+    Fill {
+        #[serde(default = "default_fraction")]
+        fraction: f32,
+        #[serde(default)]
+        outcome: Outcome,
+    },
+    /// Every ball that was inside a ring has got out of it, and at least one
+    /// did.
+    Escaped {
+        #[serde(default)]
+        outcome: Outcome,
+    },
+    /// There have been `count` collisions.
+    Collisions {
+        count: u64,
+        #[serde(default)]
+        outcome: Outcome,
+    },
+    /// `seconds` of simulated time have passed.
+    Time {
+        seconds: f32,
+        #[serde(default)]
+        outcome: Outcome,
+    },
+    /// There are `count` balls or more at once.
+    Balls {
+        count: usize,
+        #[serde(default)]
+        outcome: Outcome,
+    },
+}
+
+fn default_fraction() -> f32 {
+    0.95
+}
+
+impl EndCondition {
+    pub fn outcome(&self) -> Outcome {
+        match *self {
+            EndCondition::Fill { outcome, .. }
+            | EndCondition::Escaped { outcome }
+            | EndCondition::Collisions { outcome, .. }
+            | EndCondition::Time { outcome, .. }
+            | EndCondition::Balls { outcome, .. } => outcome,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            // A ball entirely inside a ring can't reach past its radius.
+            EndCondition::Fill { fraction, .. } if !(fraction > 0.0 && fraction <= 1.0) => Err(
+                String::from("fraction must be greater than 0 and at most 1"),
+            ),
+            EndCondition::Collisions { count: 0, .. } | EndCondition::Balls { count: 0, .. } => {
+                Err(String::from("count must be greater than 0"))
+            }
+            EndCondition::Time { seconds, .. } if seconds <= 0.0 || !seconds.is_finite() => {
+                Err(String::from("seconds must be greater than 0"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for EndCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            EndCondition::Fill { fraction, .. } => {
+                write!(f, "a ball filled {:.0}% of its ring", fraction * 100.0)
+            }
+            EndCondition::Escaped { .. } => write!(f, "every ball escaped"),
+            EndCondition::Collisions { count, .. } => write!(f, "{count} collisions"),
+            EndCondition::Time { seconds, .. } => write!(f, "{seconds}s passed"),
+            EndCondition::Balls { count, .. } => write!(f, "{count} balls"),
+        }
+    }
+}
+
+/// How and when a run ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ending {
+    /// Simulated time at the end of the step the condition was met in.
+    pub time: f32,
+    /// The first of the scene's conditions that was met.
+    pub condition: EndCondition,
+}
+
+impl Ending {
+    pub fn outcome(&self) -> Outcome {
+        self.condition.outcome()
+    }
+}
+
+impl fmt::Display for Ending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {:.2}s: {}",
+            self.outcome(),
+            self.time,
+            self.condition
+        )
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use rapier2d::{dynamics::RigidBodyHandle, geometry::ColliderHandle};
+
+use crate::physics::Physics;
+
+/// An object in a [`Simulation`](crate::sim::Simulation), by its index in
+/// [`balls`](crate::sim::Simulation::balls) or
+/// [`rings`](crate::sim::Simulation::rings).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Entity {
+    Ball(usize),
+    Ring(usize),
+}
+
+/// Finds the object a rigid body or collider belongs to without scanning
+/// every ball and ring.
+#[derive(Default)]
+pub struct EntityRegistry {
+    bodies: HashMap<RigidBodyHandle, Entity>,
+}
+
+impl EntityRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `handle` belongs to `entity`, replacing whatever it was
+    /// registered as before.
+    pub fn insert(&mut self, handle: RigidBodyHandle, entity: Entity) {
+        self.bodies.insert(handle, entity);
+    }
+
+    pub fn remove(&mut self, handle: RigidBodyHandle) -> Option<Entity> {
+        self.bodies.remove(&handle)
+    }
+
+    pub fn get(&self, handle: RigidBodyHandle) -> Option<Entity> {
+        self.bodies.get(&handle).copied()
+    }
+
+    /// The entity owning the body `collider` is attached to.
+    pub fn get_by_collider(&self, physics: &Physics, collider: ColliderHandle) -> Option<Entity> {
+        self.get(physics.parent_of(collider)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bodies.is_empty()
+    }
+}
@@ -0,0 +1,36 @@
+use rapier2d::math::{Real, Vector};
+
+/// Impact strength of an average bounce.
+pub const REFERENCE_IMPULSE: f32 = 4.0e5;
+
+/// How hard two objects hit each other, measured over a contact that just
+/// ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Impact {
+    /// Where they touched, in world space.
+    pub point: Vector<Real>,
+    /// Unit vector at the contact, pointing from the first object towards
+    /// the second.
+    pub normal: Vector<Real>,
+    /// Impulse exchanged, from the contact forces or the momentum either
+    /// body gained, whichever is larger.
+    pub strength: f32,
+    /// Speed at which the objects closed in along `normal` right before
+    /// they touched. Glancing hits are slow even between fast objects.
+    pub speed: f32,
+}
+
+impl Impact {
+    /// `strength` as a multiple of an average bounce's.
+    pub fn relative_strength(&self) -> f32 {
+        self.strength / REFERENCE_IMPULSE
+    }
+
+    /// The same impact as seen from the second object.
+    pub fn flipped(self) -> Self {
+        Self {
+            normal: -self.normal,
+            ..self
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Balls bouncing around inside rings, with a sound for every collision.
+//!
+//! A run is described by a [`Scene`](scene::Scene), either loaded from a TOML
+//! file or built in code, and driven by a [`Simulation`](sim::Simulation).
+//! Each [`step`](sim::Simulation::step) advances the physics by a fixed
+//! timestep; what happened during it can be drained as a stream of
+//! [`Collision`](sim::Collision)s, [`Escape`](sim::Escape)s,
+//! [`Break`](sim::Break)s, [`Merge`](sim::Merge)s and
+//! [`SoundTrigger`](sounds::SoundTrigger)s. Once one of the scene's end
+//! conditions is met the run has an [`Ending`](ending::Ending), won or lost.
+//!
+//! ```no_run
+//! use collide_and_sound::{scene::Scene, sim::Simulation};
+//!
+//! let mut sim = Simulation::new(&Scene::default(), 42);
+//! for _ in 0..600 {
+//!     sim.step();
+//!     for collision in sim.take_collisions() {
+//!         println!("{:.2}s: {:?} hit {:?}", collision.time, collision.first, collision.second);
+//!     }
+//! }
+//! ```
+//!
+//! Drawing goes through the [`Renderer`](render::Renderer) trait, with a CPU
+//! backend in [`canvas`] and, behind the default `sfml` feature, an SFML one
+//! plus the interactive [`App`](app::App).
+
+#[cfg(feature = "sfml")]
+pub mod app;
+pub mod assets;
+pub mod ball;
+pub mod canvas;
+pub mod ending;
+pub mod entity;
+pub mod impact;
+pub mod melody;
+pub mod mixdown;
+pub mod motion;
+pub mod music;
+pub mod physics;
+pub mod polyphony;
+pub mod render;
+pub mod ring;
+pub mod rules;
+pub mod scene;
+pub mod sim;
+pub mod sounds;
+pub mod synth;
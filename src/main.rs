@@ -1,12 +1,135 @@
-use app::App;
+use std::path::Path;
 
-mod app;
-mod ball;
-mod physics;
-mod ring;
-mod sounds;
-mod util;
+use cli::Options;
+use collide_and_sound::{
+    assets::AssetPaths,
+    ending::{Ending, Outcome},
+    melody::Melody,
+    mixdown,
+    scene::Scene,
+    sim::{RunLimit, Simulation},
+};
+
+mod cli;
 
 fn main() {
-    App::new("Collide and Sound").run();
+    let options = Options::from_env().unwrap_or_else(|err| exit_with(err));
+    let mut scene = match &options.scene {
+        Some(path) => Scene::load(path).unwrap_or_else(|err| exit_with(err)),
+        None => Scene::default(),
+    };
+    if let Some(rate) = options.physics_rate {
+        scene.physics_rate = rate;
+    }
+    let seed = options.seed.or(scene.seed).unwrap_or_else(rand::random);
+    let mut asset_paths = AssetPaths::default();
+    if let Some(dir) = &options.assets {
+        asset_paths.prepend(dir);
+    }
+    println!("seed: {seed}");
+    let melody = scene
+        .melody
+        .as_ref()
+        .map(|desc| desc.load().unwrap_or_else(|err| exit_with(err)));
+
+    if let Some(dir) = &options.record {
+        let limit = options.limit.unwrap_or(RunLimit::Seconds(10.0));
+        let mut sim = Simulation::new(&scene, seed);
+        sim.set_melody(melody);
+        let report = sim
+            .run_recording(limit, options.fps, Path::new(dir))
+            .unwrap_or_else(|err| exit_with(format!("failed to record to {dir}: {err}")));
+        print!("{report}");
+        let audio = Path::new(dir).join("audio.wav");
+        write_wav(&mut sim, &asset_paths, report.simulated_seconds, &audio);
+        println!(
+            "mux with: ffmpeg -framerate {} -i {dir}/frame_%06d.png -i {} -c:v libx264 -pix_fmt yuv420p -c:a aac out.mp4",
+            options.fps,
+            audio.display()
+        );
+        finish(&options, report.ending);
+    } else if options.headless || options.limit.is_some() || options.wav.is_some() {
+        let limit = options.limit.unwrap_or(RunLimit::Seconds(10.0));
+        let mut sim = Simulation::new(&scene, seed);
+        sim.set_melody(melody);
+        let report = sim.run_headless(limit);
+        print!("{report}");
+        if let Some(path) = &options.wav {
+            write_wav(
+                &mut sim,
+                &asset_paths,
+                report.simulated_seconds,
+                Path::new(path),
+            );
+        }
+        finish(&options, report.ending);
+    } else {
+        run_windowed(&options, &scene, seed, melody, asset_paths);
+    }
+}
+
+#[cfg(feature = "sfml")]
+fn run_windowed(
+    options: &Options,
+    scene: &Scene,
+    seed: u64,
+    melody: Option<Melody>,
+    asset_paths: AssetPaths,
+) {
+    let assets = collide_and_sound::assets::Assets::new(asset_paths);
+    let mut app = collide_and_sound::app::App::new("Collide and Sound", scene, seed, &assets)
+        .unwrap_or_else(|err| exit_with(err));
+    app.set_melody(melody);
+    if let Some(max_substeps) = options.max_substeps {
+        app.set_max_substeps(max_substeps);
+    }
+    app.set_auto_exit(options.auto_exit);
+    app.run();
+    let report = app.report();
+    print!("{report}");
+    finish(options, report.ending);
+}
+
+#[cfg(not(feature = "sfml"))]
+fn run_windowed(
+    _options: &Options,
+    _scene: &Scene,
+    _seed: u64,
+    _melody: Option<Melody>,
+    _asset_paths: AssetPaths,
+) {
+    exit_with("built without the `sfml` feature; use --headless, --wav or --record");
+}
+
+fn write_wav(sim: &mut Simulation, asset_paths: &AssetPaths, duration: f32, path: &Path) {
+    let triggers = sim.take_sound_triggers();
+    mixdown::render_wav(
+        &triggers,
+        sim.voices(),
+        sim.sound_bank(),
+        sim.polyphony(),
+        asset_paths,
+        duration,
+        path,
+    )
+    .unwrap_or_else(|err| exit_with(format!("failed to write {}: {err}", path.display())));
+}
+
+/// With `--auto-exit`, exits with a status telling how the run ended: 0 if
+/// it was won, 1 if it was lost and 3 if it was stopped before it ended.
+fn finish(options: &Options, ending: Option<Ending>) {
+    if !options.auto_exit {
+        return;
+    }
+    let status = match ending.map(|ending| ending.outcome()) {
+        Some(Outcome::Win) => 0,
+        Some(Outcome::Lose) => 1,
+        None => 3,
+    };
+    std::process::exit(status);
+}
+
+fn exit_with(err: impl std::fmt::Display) -> ! {
+    eprintln!("error: {err}");
+    std::process::exit(2);
 }
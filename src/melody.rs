@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use serde::Deserialize;
+
+use crate::music::semitones_to_ratio;
+
+/// One note of a melody, timed in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Note {
+    /// MIDI key number, 60 being middle C.
+    pub key: u8,
+    pub velocity: u8,
+    pub duration: f32,
+}
+
+/// What happens once the last note of a [`Melody`] was played.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MelodyEnd {
+    /// Start over from the first note.
+    Loop,
+    /// Play nothing for the remaining collisions.
+    Stop,
+}
+
+/// A note sequence played one note per collision, in order.
+pub struct Melody {
+    notes: Vec<Note>,
+    next: usize,
+    end: MelodyEnd,
+    sample_key: u8,
+    min_speed: f32,
+}
+
+impl Melody {
+    pub fn new(notes: Vec<Note>) -> Self {
+        Self {
+            notes,
+            next: 0,
+            end: MelodyEnd::Loop,
+            sample_key: 60,
+            min_speed: 0.0,
+        }
+    }
+
+    /// Reads the notes of `track` from a standard MIDI file. Where notes
+    /// start together only the highest one is kept, so chords collapse into
+    /// their top voice.
+    pub fn from_midi<P: AsRef<Path>>(path: P, track: usize) -> Result<Self, MelodyError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|source| MelodyError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        let smf = Smf::parse(&bytes).map_err(|source| MelodyError::Parse {
+            path: path.to_owned(),
+            source,
+        })?;
+        if track >= smf.tracks.len() {
+            return Err(MelodyError::NoTrack {
+                path: path.to_owned(),
+                track,
+                tracks: smf.tracks.len(),
+            });
+        }
+
+        let clock = Clock::new(&smf);
+        let mut held = HashMap::new();
+        let mut spans = Vec::new();
+        let mut tick = 0;
+        for event in &smf.tracks[track] {
+            tick += event.delta.as_int() as u64;
+            let TrackEventKind::Midi { channel, message } = event.kind else {
+                continue;
+            };
+            match message {
+                MidiMessage::NoteOn { key, vel } if vel > 0 => {
+                    held.insert((channel, key), (tick, vel.as_int()));
+                }
+                MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                    if let Some((start, velocity)) = held.remove(&(channel, key)) {
+                        spans.push((start, tick, key.as_int(), velocity));
+                    }
+                }
+                _ => {}
+            }
+        }
+        spans.sort_by_key(|&(start, _, key, _)| (start, std::cmp::Reverse(key)));
+        spans.dedup_by_key(|&mut (start, ..)| start);
+
+        let notes: Vec<_> = spans
+            .into_iter()
+            .map(|(start, end, key, velocity)| Note {
+                key,
+                velocity,
+                duration: clock.seconds(end) - clock.seconds(start),
+            })
+            .collect();
+        if notes.is_empty() {
+            return Err(MelodyError::NoNotes {
+                path: path.to_owned(),
+                track,
+            });
+        }
+        Ok(Self::new(notes))
+    }
+
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    pub fn set_end(&mut self, end: MelodyEnd) {
+        self.end = end;
+    }
+
+    /// The key the sound sample plays at its own pitch.
+    pub fn set_sample_key(&mut self, key: u8) {
+        self.sample_key = key;
+    }
+
+    /// Collisions slower than `speed` don't advance the melody.
+    pub fn set_min_speed(&mut self, speed: f32) {
+        self.min_speed = speed;
+    }
+
+    /// Advances to the next note for a collision at `speed`, or returns
+    /// `None` if the collision is too slow or the melody is over.
+    pub fn next_note(&mut self, speed: f32) -> Option<Note> {
+        if speed < self.min_speed || self.notes.is_empty() {
+            return None;
+        }
+        if self.next == self.notes.len() {
+            match self.end {
+                MelodyEnd::Loop => self.next = 0,
+                MelodyEnd::Stop => return None,
+            }
+        }
+        self.next += 1;
+        Some(self.notes[self.next - 1])
+    }
+
+    /// Playback rate of every note, in the order they play.
+    pub fn pitches(&self) -> Vec<f32> {
+        self.notes.iter().map(|&note| self.pitch(note)).collect()
+    }
+
+    /// Playback rate that turns the sample into `note`.
+    pub fn pitch(&self, note: Note) -> f32 {
+        semitones_to_ratio(note.key as i32 - self.sample_key as i32)
+    }
+}
+
+/// Converts MIDI ticks to seconds, following every tempo change in the file.
+struct Clock {
+    /// Tick each tempo starts at, with its seconds per tick, in order.
+    tempos: Vec<(u64, f64)>,
+}
+
+impl Clock {
+    fn new(smf: &Smf) -> Self {
+        let ticks_per_beat = match smf.header.timing {
+            Timing::Metrical(ticks) => ticks.as_int() as f64,
+            Timing::Timecode(fps, subframes) => {
+                let per_tick = 1.0 / (fps.as_f32() as f64 * subframes as f64);
+                return Self {
+                    tempos: vec![(0, per_tick)],
+                };
+            }
+        };
+        let mut tempos = vec![(0, 0.5 / ticks_per_beat)];
+        for track in &smf.tracks {
+            let mut tick = 0;
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                if let TrackEventKind::Meta(MetaMessage::Tempo(micros)) = event.kind {
+                    tempos.push((tick, micros.as_int() as f64 / 1e6 / ticks_per_beat));
+                }
+            }
+        }
+        tempos.sort_by_key(|&(tick, _)| tick);
+        Self { tempos }
+    }
+
+    fn seconds(&self, tick: u64) -> f32 {
+        let mut seconds = 0.0;
+        for (i, &(start, per_tick)) in self.tempos.iter().enumerate() {
+            if start >= tick {
+                break;
+            }
+            let end = self
+                .tempos
+                .get(i + 1)
+                .map_or(tick, |&(next, _)| next.min(tick));
+            seconds += (end - start) as f64 * per_tick;
+        }
+        seconds as f32
+    }
+}
+
+#[derive(Debug)]
+pub enum MelodyError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: midly::Error,
+    },
+    NoTrack {
+        path: PathBuf,
+        track: usize,
+        tracks: usize,
+    },
+    NoNotes {
+        path: PathBuf,
+        track: usize,
+    },
+}
+
+impl fmt::Display for MelodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => {
+                write!(f, "failed to read melody {}: {source}", path.display())
+            }
+            Self::Parse { path, source } => {
+                write!(f, "failed to parse melody {}: {source}", path.display())
+            }
+            Self::NoTrack {
+                path,
+                track,
+                tracks,
+            } => write!(
+                f,
+                "melody {} has no track {track}, only {tracks}",
+                path.display()
+            ),
+            Self::NoNotes { path, track } => {
+                write!(f, "track {track} of melody {} has no notes", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for MelodyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { source, .. } => Some(source),
+            Self::NoTrack { .. } | Self::NoNotes { .. } => None,
+        }
+    }
+}
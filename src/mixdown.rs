@@ -0,0 +1,318 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    f32::consts::{FRAC_PI_4, SQRT_2},
+    fmt,
+    path::Path,
+};
+
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+
+use crate::{
+    assets::{AssetError, AssetPaths},
+    polyphony::{Allocation, PlayingVoice, Polyphony},
+    sounds::{SoundBank, SoundTrigger},
+    synth::{cents_to_pitch, pitch_in_cents, Voices},
+};
+
+/// Decoded audio kept as interleaved samples in `[-1, 1]`.
+pub struct Samples {
+    data: Vec<f32>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl Samples {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> hound::Result<Self> {
+        let mut reader = WavReader::open(path)?;
+        let spec = reader.spec();
+        let data = match spec.sample_format {
+            SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+            SampleFormat::Int => {
+                let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 / scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        Ok(Self {
+            data,
+            channels: spec.channels,
+            sample_rate: spec.sample_rate,
+        })
+    }
+
+    /// Wraps mono samples, such as a rendered [`Voice`](crate::synth::Voice).
+    pub fn from_mono(data: Vec<f32>, sample_rate: u32) -> Self {
+        Self {
+            data,
+            channels: 1,
+            sample_rate,
+        }
+    }
+
+    fn frame_count(&self) -> usize {
+        self.data.len() / self.channels as usize
+    }
+
+    /// How long the samples last played back at `pitch`.
+    fn seconds(&self, pitch: f32) -> f32 {
+        if pitch <= 0.0 {
+            return 0.0;
+        }
+        self.frame_count() as f32 / self.sample_rate as f32 / pitch
+    }
+
+    fn frame(&self, index: usize) -> (f32, f32) {
+        let start = index * self.channels as usize;
+        match self.channels {
+            1 => (self.data[start], self.data[start]),
+            _ => (self.data[start], self.data[start + 1]),
+        }
+    }
+
+    /// Left and right value at a fractional frame `position`, linearly
+    /// interpolated between the two nearest frames.
+    fn frame_at(&self, position: f64) -> (f32, f32) {
+        let index = position as usize;
+        let t = (position - index as f64) as f32;
+        let (l0, r0) = self.frame(index);
+        let (l1, r1) = self.frame((index + 1).min(self.frame_count() - 1));
+        (l0 + (l1 - l0) * t, r0 + (r1 - r0) * t)
+    }
+}
+
+/// A stereo timeline that triggered sounds are mixed into, one output frame
+/// per sample period.
+pub struct Mixdown {
+    sample_rate: u32,
+    frames: Vec<(f32, f32)>,
+}
+
+impl Mixdown {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Mixes `samples` in starting at `start` seconds. `pitch` scales the
+    /// playback rate the same way `SoundSource::set_pitch` does, and `volume`
+    /// uses SFML's 0–100 scale. `pan` goes from -1 (left) to 1 (right) with
+    /// equal loudness across. With a `duration` the sound is faded out
+    /// quickly once that many seconds have passed.
+    pub fn add(
+        &mut self,
+        samples: &Samples,
+        start: f32,
+        pitch: f32,
+        volume: f32,
+        pan: f32,
+        duration: Option<f32>,
+    ) {
+        if pitch <= 0.0 || samples.frame_count() == 0 {
+            return;
+        }
+        let gain = volume / 100.0;
+        let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+        let (left_gain, right_gain) = (angle.cos() * SQRT_2, angle.sin() * SQRT_2);
+        let step = samples.sample_rate as f64 * pitch as f64 / self.sample_rate as f64;
+        let last = (samples.frame_count() - 1) as f64;
+        let release = (RELEASE_SECONDS * self.sample_rate as f32) as usize;
+        let (length, release_at) = match duration {
+            Some(duration) => {
+                let release_at = (duration.max(0.0) * self.sample_rate as f32) as usize;
+                let length = (last / step) as usize + 1;
+                (length.min(release_at + release), release_at)
+            }
+            None => ((last / step) as usize + 1, usize::MAX),
+        };
+        let offset = (start as f64 * self.sample_rate as f64).round() as usize;
+        self.extend_to(offset + length);
+
+        for (i, frame) in self.frames[offset..offset + length].iter_mut().enumerate() {
+            let (left, right) = samples.frame_at(i as f64 * step);
+            let fade = match i.checked_sub(release_at) {
+                Some(released) => 1.0 - released as f32 / release as f32,
+                None => 1.0,
+            };
+            frame.0 += left * left_gain * gain * fade;
+            frame.1 += right * right_gain * gain * fade;
+        }
+    }
+
+    /// Pads the timeline with silence up to `seconds`.
+    pub fn extend_to_seconds(&mut self, seconds: f32) {
+        self.extend_to((seconds as f64 * self.sample_rate as f64).ceil() as usize);
+    }
+
+    fn extend_to(&mut self, length: usize) {
+        if self.frames.len() < length {
+            self.frames.resize(length, (0.0, 0.0));
+        }
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> hound::Result<()> {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(path, spec)?;
+        for &(left, right) in &self.frames {
+            writer.write_sample(to_i16(left))?;
+            writer.write_sample(to_i16(right))?;
+        }
+        writer.finalize()
+    }
+}
+
+/// How long a sound cut short by its duration takes to fade out, to avoid
+/// clicks.
+const RELEASE_SECONDS: f32 = 0.005;
+
+fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+/// Mixes every trigger of a run into a stereo WAV file at least `duration`
+/// seconds long, synthesizing the sounds that have one of `voices` and
+/// reading the others from `bank` or the bundled samples found in `paths`.
+/// Triggers give way to each other by `polyphony` as they would playing
+/// live.
+pub fn render_wav<P: AsRef<Path>>(
+    triggers: &[SoundTrigger],
+    voices: &Voices,
+    bank: &SoundBank,
+    polyphony: &Polyphony,
+    paths: &AssetPaths,
+    duration: f32,
+    path: P,
+) -> Result<(), MixdownError> {
+    const SAMPLE_RATE: u32 = 44100;
+    let mut sounds = Vec::new();
+    let mut samples = HashMap::new();
+    let mut rendered = HashMap::new();
+    let mut mixed: Vec<Mixed> = Vec::new();
+    // Indices in `mixed` of the sounds still playing, oldest first.
+    let mut playing: Vec<usize> = Vec::new();
+    for trigger in triggers {
+        let (sound, pitch) = match voices.get(trigger.sound) {
+            Some(voice) => {
+                let Some(cents) = pitch_in_cents(trigger.pitch) else {
+                    continue;
+                };
+                let sound = *rendered.entry((trigger.sound, cents)).or_insert_with(|| {
+                    sounds.push(Samples::from_mono(
+                        voice.render(cents_to_pitch(cents), SAMPLE_RATE),
+                        SAMPLE_RATE,
+                    ));
+                    sounds.len() - 1
+                });
+                (sound, 1.0)
+            }
+            None => {
+                let file = bank.file(trigger.sound, paths)?;
+                let sound = match samples.entry(file) {
+                    Entry::Occupied(entry) => *entry.get(),
+                    Entry::Vacant(entry) => {
+                        let sound =
+                            Samples::from_file(entry.key()).map_err(|err| AssetError::Load {
+                                path: entry.key().clone(),
+                                reason: err.to_string(),
+                            })?;
+                        sounds.push(sound);
+                        *entry.insert(sounds.len() - 1)
+                    }
+                };
+                (sound, trigger.pitch)
+            }
+        };
+
+        playing.retain(|&i| mixed[i].trigger.time + mixed[i].length > trigger.time);
+        let voices: Vec<_> = playing
+            .iter()
+            .map(|&i| PlayingVoice {
+                sound: mixed[i].trigger.sound,
+                volume: mixed[i].trigger.volume,
+            })
+            .collect();
+        match polyphony.allocate(&voices, trigger.sound, trigger.volume) {
+            Allocation::Play => {}
+            Allocation::Steal(index) => {
+                let stolen = &mut mixed[playing.remove(index)];
+                stolen.length = trigger.time - stolen.trigger.time;
+            }
+            Allocation::Drop => continue,
+        }
+        let length = sounds[sound].seconds(pitch);
+        playing.push(mixed.len());
+        mixed.push(Mixed {
+            trigger,
+            sound,
+            pitch,
+            length: trigger
+                .duration
+                .map_or(length, |duration| duration.min(length)),
+        });
+    }
+
+    let mut mixdown = Mixdown::new(SAMPLE_RATE);
+    mixdown.extend_to_seconds(duration);
+    // A sound that had its place taken the moment it started never sounds.
+    for sound in mixed.iter().filter(|sound| sound.length > 0.0) {
+        mixdown.add(
+            &sounds[sound.sound],
+            sound.trigger.time,
+            sound.pitch,
+            sound.trigger.volume,
+            sound.trigger.pan,
+            Some(sound.length),
+        );
+    }
+    mixdown.write(path).map_err(MixdownError::Write)
+}
+
+/// A trigger that got to play in [`render_wav`].
+struct Mixed<'a> {
+    trigger: &'a SoundTrigger,
+    /// Index of the samples it plays.
+    sound: usize,
+    pitch: f32,
+    /// Seconds it plays for, until it ends, its duration is up or another
+    /// sound takes its place.
+    length: f32,
+}
+
+#[derive(Debug)]
+pub enum MixdownError {
+    /// A sample to mix in could not be found or read.
+    Asset(AssetError),
+    Write(hound::Error),
+}
+
+impl From<AssetError> for MixdownError {
+    fn from(err: AssetError) -> Self {
+        Self::Asset(err)
+    }
+}
+
+impl fmt::Display for MixdownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Asset(err) => err.fmt(f),
+            Self::Write(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for MixdownError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Asset(err) => Some(err),
+            Self::Write(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::f32::consts::TAU;
+
+use rapier2d::math::{Real, Vector};
+
+/// Where a moving object is relative to where it started: how far it moved
+/// and how far it turned, in radians clockwise on screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub offset: Vector<Real>,
+    pub angle: f32,
+}
+
+impl Pose {
+    pub const START: Pose = Pose {
+        offset: Vector::new(0.0, 0.0),
+        angle: 0.0,
+    };
+
+    fn lerp(&self, other: &Pose, t: f32) -> Pose {
+        Pose {
+            offset: self.offset.lerp(&other.offset, t),
+            angle: self.angle + (other.angle - self.angle) * t,
+        }
+    }
+}
+
+/// A pose to reach at a point in time along a [`Motion::Path`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f32,
+    pub pose: Pose,
+}
+
+/// How a kinematic object moves over simulated time. It is driven to the
+/// pose for each step rather than pushed, so whatever it hits still picks up
+/// its velocity.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Motion {
+    /// Turns at a constant `speed` in radians per second.
+    Spin { speed: f32 },
+    /// Swings back and forth through the start, by up to `amplitude` radians
+    /// and `offset`, once every `period` seconds.
+    Oscillate {
+        amplitude: f32,
+        offset: Vector<Real>,
+        period: f32,
+    },
+    /// Moves in straight lines between `keyframes`, sorted by time, starting
+    /// from the start pose at time 0 and holding still after the last one
+    /// unless it repeats.
+    Path {
+        keyframes: Vec<Keyframe>,
+        repeat: bool,
+    },
+}
+
+impl Motion {
+    /// The pose `time` seconds into the run.
+    pub fn pose(&self, time: f32) -> Pose {
+        match self {
+            Motion::Spin { speed } => Pose {
+                offset: Vector::zeros(),
+                angle: speed * time,
+            },
+            Motion::Oscillate {
+                amplitude,
+                offset,
+                period,
+            } => {
+                let swing = (time / period * TAU).sin();
+                Pose {
+                    offset: offset * swing,
+                    angle: amplitude * swing,
+                }
+            }
+            Motion::Path { keyframes, repeat } => path_pose(keyframes, *repeat, time),
+        }
+    }
+}
+
+fn path_pose(keyframes: &[Keyframe], repeat: bool, time: f32) -> Pose {
+    let Some(last) = keyframes.last() else {
+        return Pose::START;
+    };
+    let time = if repeat && last.time > 0.0 {
+        time.rem_euclid(last.time)
+    } else {
+        time
+    };
+    let mut previous = Keyframe {
+        time: 0.0,
+        pose: Pose::START,
+    };
+    for keyframe in keyframes {
+        if time < keyframe.time {
+            let t = (time - previous.time) / (keyframe.time - previous.time);
+            return previous.pose.lerp(&keyframe.pose, t);
+        }
+        previous = *keyframe;
+    }
+    last.pose
+}
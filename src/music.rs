@@ -0,0 +1,137 @@
+use serde::Deserialize;
+
+/// Semitone offsets from the root that make up one octave of a scale.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "ScaleDesc")]
+pub enum Scale {
+    Major,
+    Minor,
+    Pentatonic,
+    Chromatic,
+    /// Intervals in semitones, each below 12, starting from the root.
+    Custom(Vec<u8>),
+}
+
+impl Scale {
+    pub fn intervals(&self) -> &[u8] {
+        match self {
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::Pentatonic => &[0, 2, 4, 7, 9],
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Custom(intervals) => intervals,
+        }
+    }
+}
+
+/// A scale as written in a scene file: either its name or a list of
+/// intervals.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDesc {
+    Name(String),
+    Intervals(Vec<u8>),
+}
+
+impl TryFrom<ScaleDesc> for Scale {
+    type Error = String;
+
+    fn try_from(desc: ScaleDesc) -> Result<Self, Self::Error> {
+        match desc {
+            ScaleDesc::Name(name) => match name.as_str() {
+                "major" => Ok(Scale::Major),
+                "minor" => Ok(Scale::Minor),
+                "pentatonic" => Ok(Scale::Pentatonic),
+                "chromatic" => Ok(Scale::Chromatic),
+                _ => Err(format!(
+                    "unknown scale `{name}`, expected major, minor, pentatonic, chromatic \
+                     or a list of intervals"
+                )),
+            },
+            ScaleDesc::Intervals(mut intervals) => {
+                if intervals.is_empty() || intervals.iter().any(|&interval| interval >= 12) {
+                    return Err(String::from(
+                        "scale intervals must be a non-empty list of semitones below 12",
+                    ));
+                }
+                intervals.sort_unstable();
+                intervals.dedup();
+                Ok(Scale::Custom(intervals))
+            }
+        }
+    }
+}
+
+/// Snaps bounce pitches to the notes of a scale, so collisions play a melody
+/// instead of sliding around continuously.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Tuning {
+    pub scale: Scale,
+    /// Semitones between the sample's own pitch and the scale's root.
+    #[serde(default)]
+    pub root: i32,
+    /// Lowest and highest octave around the root that notes are picked from;
+    /// the range ends on the root of the highest one.
+    #[serde(default = "default_octaves")]
+    pub octaves: [i32; 2],
+}
+
+impl Tuning {
+    pub fn new(scale: Scale) -> Self {
+        Self {
+            scale,
+            root: 0,
+            octaves: default_octaves(),
+        }
+    }
+
+    /// Playback rate for the note picked by `level`, from 0 for the lowest
+    /// note in range to 1 for the highest, tuned to equal temperament.
+    pub fn pitch(&self, level: f32) -> f32 {
+        let notes = self.note_count();
+        let degree = (level.clamp(0.0, 1.0) * (notes - 1) as f32).round() as usize;
+        self.note_pitch(degree)
+    }
+
+    /// Playback rate of every note in range, from the lowest up.
+    pub fn pitches(&self) -> Vec<f32> {
+        (0..self.note_count())
+            .map(|degree| self.note_pitch(degree))
+            .collect()
+    }
+
+    fn note_count(&self) -> usize {
+        let [low, high] = self.octaves;
+        self.scale.intervals().len() * (high - low) as usize + 1
+    }
+
+    fn note_pitch(&self, degree: usize) -> f32 {
+        let intervals = self.scale.intervals();
+        if intervals.is_empty() {
+            return semitones_to_ratio(self.root);
+        }
+        let octave = self.octaves[0] + (degree / intervals.len()) as i32;
+        let interval = intervals[degree % intervals.len()] as i32;
+        semitones_to_ratio(self.root + octave * 12 + interval)
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.scale.intervals().is_empty() {
+            return Err(String::from("scale must have at least one note"));
+        }
+        if self.octaves[0] >= self.octaves[1] {
+            return Err(String::from("octaves must go from a lower to a higher one"));
+        }
+        Ok(())
+    }
+}
+
+/// Equal-temperament playback rate `semitones` away from the original.
+pub fn semitones_to_ratio(semitones: i32) -> f32 {
+    2f32.powf(semitones as f32 / 12.0)
+}
+
+fn default_octaves() -> [i32; 2] {
+    [-1, 2]
+}
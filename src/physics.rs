@@ -1,6 +1,9 @@
 use crossbeam::channel::Receiver;
 use rapier2d::{na::Matrix2x1, prelude::*};
 
+/// Gravity a new [`Physics`] starts with, pointing down the screen.
+pub const DEFAULT_GRAVITY: Real = 9.81 * 25.;
+
 pub struct Physics {
     gravity: Matrix2x1<Real>,
     integration_parameters: IntegrationParameters,
@@ -22,8 +25,11 @@ pub struct Physics {
 
 impl Physics {
     pub fn new() -> Self {
-        let gravity = vector![0.0, 9.81 * 25.];
-        let integration_parameters = IntegrationParameters::default();
+        let gravity = vector![0.0, DEFAULT_GRAVITY];
+        let integration_parameters = IntegrationParameters {
+            dt: 1.0 / 60.0,
+            ..Default::default()
+        };
         let physics_pipeline = PhysicsPipeline::new();
         let island_manager = IslandManager::new();
         let broad_phase = BroadPhase::new();
@@ -79,6 +85,23 @@ impl Physics {
         );
     }
 
+    pub fn gravity(&self) -> Vector<Real> {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, [x, y]: [Real; 2]) {
+        self.gravity = vector![x, y];
+    }
+
+    /// Simulated seconds advanced by a single [`Physics::step`].
+    pub fn timestep(&self) -> Real {
+        self.integration_parameters.dt
+    }
+
+    pub fn set_timestep(&mut self, dt: Real) {
+        self.integration_parameters.dt = dt;
+    }
+
     pub fn insert_body(&mut self, rb: RigidBody, collider: Collider) -> RigidBodyHandle {
         let rbhandle = self.rigidbody_set.insert(rb);
         let _ = self.collider_set
@@ -86,6 +109,28 @@ impl Physics {
         rbhandle
     }
 
+    /// Bodies with a collider containing `point`, as the colliders were
+    /// placed at the end of the last step.
+    pub fn bodies_at(&self, point: Point<Real>) -> Vec<RigidBodyHandle> {
+        let mut bodies = Vec::new();
+        self.query_pipeline.intersections_with_point(
+            &self.rigidbody_set,
+            &self.collider_set,
+            &point,
+            QueryFilter::default(),
+            |handle| {
+                bodies.extend(self.parent_of(handle));
+                true
+            },
+        );
+        bodies
+    }
+
+    /// The rigid body `collider` is attached to, if it still exists.
+    pub fn parent_of(&self, collider: ColliderHandle) -> Option<RigidBodyHandle> {
+        self.collider_set.get(collider)?.parent()
+    }
+
     pub fn get_collision_events(&mut self) -> Vec<CollisionEvent> {
         let mut events = Vec::new();
         while let Ok(event) = self.event_receiver.0.try_recv() {
@@ -94,7 +139,6 @@ impl Physics {
         events
     }
 
-    #[allow(dead_code)]
     pub fn get_contact_force_events(&mut self) -> Vec<ContactForceEvent> {
         let mut events = Vec::new();
         while let Ok(event) = self.event_receiver.1.try_recv() {
@@ -103,6 +147,44 @@ impl Physics {
         events
     }
 
+    /// Where two colliders currently touch, averaged over their contact
+    /// points, and the contact normal pointing from `collider1` towards
+    /// `collider2`, in world space.
+    pub fn contact(
+        &self,
+        collider1: ColliderHandle,
+        collider2: ColliderHandle,
+    ) -> Option<(Point<Real>, Vector<Real>)> {
+        let pair = self.narrow_phase.contact_pair(collider1, collider2)?;
+        let contacts: Vec<_> = pair
+            .manifolds
+            .iter()
+            .flat_map(|manifold| {
+                let normal = manifold.data.normal;
+                manifold
+                    .data
+                    .solver_contacts
+                    .iter()
+                    .map(move |contact| (contact.point.coords, normal))
+            })
+            .collect();
+        if contacts.is_empty() {
+            return None;
+        }
+        let (points, normals): (Vector<Real>, Vector<Real>) = contacts
+            .iter()
+            .fold((Vector::zeros(), Vector::zeros()), |(points, normals), (point, normal)| {
+                (points + point, normals + normal)
+            });
+        let normal = normals.try_normalize(Real::EPSILON)?;
+        let normal = if pair.collider1 == collider1 {
+            normal
+        } else {
+            -normal
+        };
+        Some((Point::from(points / contacts.len() as Real), normal))
+    }
+
     pub fn replace_collider(
         &mut self,
         rbhandle: RigidBodyHandle,
@@ -120,6 +202,46 @@ impl Physics {
             .insert_with_parent(new_collider, rbhandle, &mut self.rigidbody_set);
     }
 
+    /// Swaps whatever colliders `rbhandle` has for `new_collider`, for when the
+    /// caller no longer knows which handle the current one has.
+    pub fn set_collider(&mut self, rbhandle: RigidBodyHandle, new_collider: Collider) {
+        let old_colliders = match self.rigidbody_set.get(rbhandle) {
+            Some(rb) => rb.colliders().to_vec(),
+            None => return,
+        };
+        for handle in old_colliders {
+            self.collider_set.remove(
+                handle,
+                &mut self.island_manager,
+                &mut self.rigidbody_set,
+                true,
+            );
+            self.removed_colliders.push(handle);
+        }
+        let _ = self.collider_set
+            .insert_with_parent(new_collider, rbhandle, &mut self.rigidbody_set);
+    }
+
+    /// Takes `rbhandle` out of the world along with its colliders and any
+    /// joints attached to it. Collisions they were in count as removed for
+    /// the rest of the step. Returns whether there was such a body.
+    pub fn remove_body(&mut self, rbhandle: RigidBodyHandle) -> bool {
+        let colliders = match self.rigidbody_set.get(rbhandle) {
+            Some(rb) => rb.colliders().to_vec(),
+            None => return false,
+        };
+        self.rigidbody_set.remove(
+            rbhandle,
+            &mut self.island_manager,
+            &mut self.collider_set,
+            &mut self.impulse_joint_set,
+            &mut self.multibody_joint_set,
+            true,
+        );
+        self.removed_colliders.extend(colliders);
+        true
+    }
+
     pub fn is_collider_removed(&self, handle: ColliderHandle) -> bool {
         self.removed_colliders.contains(&handle)
     }
@@ -129,6 +251,12 @@ impl Physics {
     }
 }
 
+impl Default for Physics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub trait PhysicsObject {
     fn insert_into_physics(&mut self, rbtype: RigidBodyType, physics: &mut Physics);
 }
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::sounds::{SoundBank, SoundType};
+
+/// SFML refuses to create more sound sources than this at once.
+pub const MAX_SOURCES: usize = 256;
+
+/// Which playing sound makes room when there are too many.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StealPolicy {
+    /// The one that started first.
+    #[default]
+    Oldest,
+    /// The one started at the lowest volume, unless the new sound is quieter
+    /// still.
+    Quietest,
+    /// The oldest one of the lowest priority, unless the new sound's
+    /// priority is lower than all of them.
+    Priority,
+}
+
+/// How many sounds may play at once and what gives way when a new one
+/// doesn't fit.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Polyphony {
+    /// Sounds playing at once, across all types.
+    pub max_voices: usize,
+    pub steal: StealPolicy,
+    /// Sounds of one kind playing at once, by name: `bounce`, `ball`,
+    /// `ring`, `shatter` or a sample in the scene's sound bank. A new sound
+    /// over its limit replaces one of the same kind.
+    pub limits: HashMap<String, usize>,
+    /// Used by [`StealPolicy::Priority`]; higher is kept longer. Named like
+    /// `limits`; sounds left out have priority 0.
+    pub priority: HashMap<String, i32>,
+    /// Seconds a ball stays silent after making a sound, so one resting on a
+    /// ring doesn't rattle off a sound every step.
+    pub retrigger: f32,
+    /// Names of the bank sounds, by their index in the bank.
+    #[serde(skip)]
+    bank: Vec<String>,
+}
+
+impl Default for Polyphony {
+    fn default() -> Self {
+        Self {
+            max_voices: 64,
+            steal: StealPolicy::default(),
+            limits: HashMap::new(),
+            priority: HashMap::new(),
+            retrigger: 0.05,
+            bank: Vec::new(),
+        }
+    }
+}
+
+/// A sound that is playing, as far as [`Polyphony::allocate`] cares.
+#[derive(Clone, Copy, Debug)]
+pub struct PlayingVoice {
+    pub sound: SoundType,
+    pub volume: f32,
+}
+
+/// What to do with a new sound.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Allocation {
+    Play,
+    /// Stop the playing sound at this index first.
+    Steal(usize),
+    Drop,
+}
+
+impl Polyphony {
+    /// Looks limits and priorities of [`SoundType::Bank`] sounds up by their
+    /// names in `bank`.
+    pub fn set_bank(&mut self, bank: &SoundBank) {
+        self.bank = bank.names().to_vec();
+    }
+
+    /// Decides whether a new `sound` at `volume` may play next to `playing`,
+    /// which is ordered from the oldest sound to the newest.
+    pub fn allocate(&self, playing: &[PlayingVoice], sound: SoundType, volume: f32) -> Allocation {
+        let new = PlayingVoice { sound, volume };
+        if let Some(&limit) = self.name(sound).and_then(|name| self.limits.get(name)) {
+            let same_type: Vec<_> = (0..playing.len())
+                .filter(|&i| playing[i].sound == sound)
+                .collect();
+            if same_type.len() >= limit {
+                return self.pick_victim(playing, &same_type, new);
+            }
+        }
+        if playing.len() >= self.max_voices {
+            let all: Vec<_> = (0..playing.len()).collect();
+            return self.pick_victim(playing, &all, new);
+        }
+        Allocation::Play
+    }
+
+    fn pick_victim(
+        &self,
+        playing: &[PlayingVoice],
+        candidates: &[usize],
+        new: PlayingVoice,
+    ) -> Allocation {
+        let victim = match self.steal {
+            StealPolicy::Oldest => candidates.first().copied(),
+            StealPolicy::Quietest => candidates
+                .iter()
+                .copied()
+                .min_by(|&a, &b| playing[a].volume.total_cmp(&playing[b].volume))
+                .filter(|&i| playing[i].volume <= new.volume),
+            StealPolicy::Priority => candidates
+                .iter()
+                .copied()
+                .min_by_key(|&i| self.priority_of(playing[i].sound))
+                .filter(|&i| self.priority_of(playing[i].sound) <= self.priority_of(new.sound)),
+        };
+        victim.map_or(Allocation::Drop, Allocation::Steal)
+    }
+
+    fn priority_of(&self, sound: SoundType) -> i32 {
+        self.name(sound)
+            .and_then(|name| self.priority.get(name))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// What `sound` is called in `limits` and `priority`.
+    fn name(&self, sound: SoundType) -> Option<&str> {
+        match sound {
+            SoundType::Bank(index) => self.bank.get(index).map(String::as_str),
+            _ => sound.name(),
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.max_voices == 0 || self.max_voices > MAX_SOURCES {
+            return Err(format!("max_voices must be between 1 and {MAX_SOURCES}"));
+        }
+        if self.retrigger < 0.0 {
+            return Err(String::from("retrigger must not be negative"));
+        }
+        Ok(())
+    }
+}
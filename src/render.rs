@@ -0,0 +1,70 @@
+use rapier2d::math::{Real, Vector};
+
+#[cfg(feature = "sfml")]
+pub mod sfml;
+
+/// Anything that can put the simulation on screen or into an image. Objects
+/// describe themselves as plain shapes and leave the drawing to the backend.
+pub trait Renderer {
+    fn clear(&mut self, color: Color);
+    fn draw_circle(&mut self, circle: &Circle);
+    fn draw_arc(&mut self, arc: &Arc);
+    /// Blends `color` over everything drawn so far.
+    fn tint(&mut self, color: Color);
+}
+
+pub trait Drawable {
+    /// `alpha` is how far between the previous and the current physics step
+    /// the frame is being drawn, for objects that interpolate their motion.
+    fn draw(&self, renderer: &mut dyn Renderer, alpha: f32);
+}
+
+/// A circle drawn the way `sfml::graphics::CircleShape` draws one: filled up
+/// to `radius`, with an outline `outline_thickness` wide growing outwards.
+pub struct Circle {
+    pub center: Vector<Real>,
+    pub radius: f32,
+    pub outline_thickness: f32,
+    pub fill: Color,
+    pub outline: Color,
+    /// Polygon resolution for backends that tessellate.
+    #[cfg_attr(not(feature = "sfml"), allow(dead_code))]
+    pub point_count: usize,
+}
+
+/// A piece of a circle's outline: the band from `radius` out to `radius +
+/// thickness`, from angle `start` over `length` radians. Angles go clockwise
+/// on screen from the positive x axis.
+pub struct Arc {
+    pub center: Vector<Real>,
+    pub radius: f32,
+    pub thickness: f32,
+    pub start: f32,
+    pub length: f32,
+    pub color: Color,
+    /// Polygon resolution of a full circle, for backends that tessellate.
+    #[cfg_attr(not(feature = "sfml"), allow(dead_code))]
+    pub point_count: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const BLACK: Self = Self::rgb(0, 0, 0);
+    pub const WHITE: Self = Self::rgb(255, 255, 255);
+    pub const TRANSPARENT: Self = Self::rgba(0, 0, 0, 0);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::rgba(r, g, b, 255)
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+}
@@ -0,0 +1,75 @@
+use std::f32::consts::TAU;
+
+use sfml::graphics::{
+    self, CircleShape, PrimitiveType, RectangleShape, RenderStates, RenderTarget, Shape,
+    Transformable, Vertex,
+};
+
+use super::{Arc, Circle, Color, Renderer};
+
+/// Draws through SFML onto a window or any other render target.
+pub struct SfmlRenderer<'t> {
+    target: &'t mut dyn RenderTarget,
+    shape: CircleShape<'static>,
+}
+
+impl<'t> SfmlRenderer<'t> {
+    pub fn new(target: &'t mut dyn RenderTarget) -> Self {
+        Self {
+            target,
+            shape: CircleShape::default(),
+        }
+    }
+}
+
+impl Renderer for SfmlRenderer<'_> {
+    fn clear(&mut self, color: Color) {
+        self.target.clear(color.into());
+    }
+
+    fn draw_circle(&mut self, circle: &Circle) {
+        self.shape.set_position((circle.center.x, circle.center.y));
+        self.shape.set_radius(circle.radius);
+        self.shape.set_origin((circle.radius, circle.radius));
+        self.shape.set_fill_color(circle.fill.into());
+        self.shape.set_outline_color(circle.outline.into());
+        self.shape.set_outline_thickness(circle.outline_thickness);
+        self.shape.set_point_count(circle.point_count);
+        self.target
+            .draw_circle_shape(&self.shape, &RenderStates::default());
+    }
+
+    fn draw_arc(&mut self, arc: &Arc) {
+        let steps = ((arc.length / TAU * arc.point_count as f32).ceil() as usize).max(1);
+        let color = arc.color.into();
+        let vertices: Vec<_> = (0..=steps)
+            .flat_map(|step| {
+                let angle = arc.start + arc.length * step as f32 / steps as f32;
+                let direction = (angle.cos(), angle.sin());
+                [arc.radius, arc.radius + arc.thickness].map(|radius| {
+                    let x = arc.center.x + direction.0 * radius;
+                    let y = arc.center.y + direction.1 * radius;
+                    Vertex::with_pos_color((x, y).into(), color)
+                })
+            })
+            .collect();
+        self.target.draw_primitives(
+            &vertices,
+            PrimitiveType::TRIANGLE_STRIP,
+            &RenderStates::default(),
+        );
+    }
+
+    fn tint(&mut self, color: Color) {
+        let mut rectangle = RectangleShape::with_size(self.target.size().as_other());
+        rectangle.set_fill_color(color.into());
+        self.target
+            .draw_rectangle_shape(&rectangle, &RenderStates::default());
+    }
+}
+
+impl From<Color> for graphics::Color {
+    fn from(color: Color) -> Self {
+        graphics::Color::rgba(color.r, color.g, color.b, color.a)
+    }
+}
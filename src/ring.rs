@@ -1,65 +1,145 @@
 #![allow(unused)]
 
-use std::f32::consts::PI;
+use std::f32::consts::{PI, TAU};
 
 use rand::Rng;
 use rapier2d::{
     dynamics::{RigidBodyBuilder, RigidBodyHandle, RigidBodyType},
     geometry::{Collider, ColliderBuilder},
+    math::{Isometry, Real, Vector},
     na::Point2,
     pipeline::ActiveEvents,
 };
-use sfml::{
-    graphics::{CircleShape, Color, Drawable, Shape, Transformable},
-    system::Vector2f,
-};
+use serde::Deserialize;
 
 use crate::{
+    motion::Motion,
     physics::{Physics, PhysicsObject},
-    util::{self, ToNaMat2x1},
+    render::{Arc, Circle, Color, Drawable, Renderer},
 };
 
-pub struct Ring<'s> {
-    pub shape: CircleShape<'s>,
+/// How long the pieces of a broken ring stay visible, in seconds.
+const SHATTER_SECONDS: f32 = 0.8;
+/// How fast the pieces fly apart, in pixels per second.
+const SHATTER_SPEED: f32 = 120.0;
+/// How many pieces a whole ring breaks into.
+const SHARDS_PER_TURN: f32 = 24.0;
+
+pub struct Ring {
+    position: Vector<Real>,
+    radius: f32,
+    outline_thickness: f32,
+    outline_color: Color,
+    point_count: usize,
     rb_handle: Option<RigidBodyHandle>,
+    restitution: f32,
+    gaps: Vec<Gap>,
+    motion: Option<Motion>,
+    /// Where the ring was placed, which its motion is relative to.
+    origin: Vector<Real>,
+    /// How far the ring turned from where it was placed, clockwise on screen.
+    angle: f32,
+    previous_position: Vector<Real>,
+    previous_angle: f32,
+    /// Hits from balls left before the ring breaks, if it breaks at all.
+    health: Option<u32>,
+    break_on_escape: bool,
+    shatter: Option<Shatter>,
 }
 
-impl<'s> Ring<'s> {
-    pub fn new<P: Into<Vector2f>>(pos: P) -> Self {
-        let mut shape = CircleShape::default();
-        shape.set_position(pos);
-        shape.set_radius(100.0);
-        shape.set_origin((100.0, 100.0));
-        shape.set_fill_color(sfml::graphics::Color::TRANSPARENT);
-        shape.set_outline_color(sfml::graphics::Color::WHITE);
-        shape.set_outline_thickness(5.0);
-        shape.set_point_count(256);
-        Self {
-            shape,
-            rb_handle: None,
-        }
+/// Seconds since a ring broke, at the previous and the current step.
+#[derive(Clone, Copy, Default)]
+struct Shatter {
+    previous_age: f32,
+    age: f32,
+}
+
+/// An opening in a ring, in radians going clockwise on screen from the
+/// positive x axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gap {
+    pub start: f32,
+    pub length: f32,
+}
+
+impl Ring {
+    pub fn new<P: Into<Vector<Real>>>(pos: P) -> Self {
+        Self::new_with_radius(pos, 100.0)
     }
 
-    pub fn new_with_size<P: Into<Vector2f>>(pos: P, size: RingSize) -> Self {
-        let mut shape = CircleShape::default();
+    pub fn new_with_size<P: Into<Vector<Real>>>(pos: P, size: RingSize) -> Self {
         let radius = match size {
             RingSize::Small => 15.0,
             RingSize::Medium => 100.0,
             RingSize::Large => 150.0,
         };
-        shape.set_position(pos);
-        shape.set_radius(radius);
-        shape.set_origin((radius, radius));
-        shape.set_fill_color(sfml::graphics::Color::TRANSPARENT);
-        shape.set_outline_color(sfml::graphics::Color::WHITE);
-        shape.set_outline_thickness(5.0);
-        shape.set_point_count(256);
+        Self::new_with_radius(pos, radius)
+    }
+
+    pub fn new_with_radius<P: Into<Vector<Real>>>(pos: P, radius: f32) -> Self {
+        let position = pos.into();
         Self {
-            shape,
+            position,
+            radius,
+            outline_thickness: 5.0,
+            outline_color: Color::WHITE,
+            point_count: 256,
             rb_handle: None,
+            restitution: 1.035,
+            gaps: Vec::new(),
+            motion: None,
+            origin: position,
+            angle: 0.0,
+            previous_position: position,
+            previous_angle: 0.0,
+            health: None,
+            break_on_escape: false,
+            shatter: None,
         }
     }
 
+    /// Moves the ring's body towards where its motion puts it `time` seconds
+    /// into the run, to get there by the end of the next physics step. Only
+    /// kinematic bodies follow.
+    pub fn drive(&self, physics: &mut Physics, time: f32) {
+        let (Some(motion), Some(rb_handle)) = (&self.motion, self.rb_handle) else {
+            return;
+        };
+        if let Some(rb) = physics.rigidbody_set.get_mut(rb_handle) {
+            let pose = motion.pose(time);
+            rb.set_next_kinematic_position(Isometry::new(
+                self.origin + pose.offset,
+                PI / 2.0 + pose.angle,
+            ));
+        }
+    }
+
+    /// Catches up with where physics moved the ring's body, or with how far
+    /// its pieces flew once it broke.
+    pub fn update(&mut self, physics: &mut Physics) {
+        if let Some(shatter) = &mut self.shatter {
+            shatter.previous_age = shatter.age;
+            shatter.age += physics.timestep();
+        }
+        let Some(rb) = self
+            .rb_handle
+            .and_then(|rb_handle| physics.rigidbody_set.get(rb_handle))
+        else {
+            return;
+        };
+        self.previous_position = self.position;
+        self.previous_angle = self.angle;
+        self.position = rb.position().translation.vector;
+        // Count whole turns rather than wrapping, so drawing between two
+        // steps never goes the long way round.
+        let turned = rb.position().rotation.angle() - PI / 2.0 - self.angle;
+        self.angle += (turned + PI).rem_euclid(TAU) - PI;
+    }
+
+    pub fn rb_handle(&self) -> Option<RigidBodyHandle> {
+        self.rb_handle
+    }
+
     pub fn is_obj_with_handle(&self, handle: RigidBodyHandle) -> bool {
         if let Some(rb_handle) = self.rb_handle {
             rb_handle == handle
@@ -69,36 +149,168 @@ impl<'s> Ring<'s> {
     }
 
     pub fn set_outline_color(&mut self, color: Color) {
-        self.shape.set_outline_color(color);
+        self.outline_color = color;
     }
 
-    pub fn rand_outline_color(&mut self) {
-        let mut rng = rand::thread_rng();
-        self.shape.set_outline_color(Color::rgb(
+    pub fn rand_outline_color<R: Rng>(&mut self, rng: &mut R) {
+        self.set_outline_color(Color::rgb(
             rng.gen_range(10..255),
             rng.gen_range(10..255),
             rng.gen_range(10..255),
         ));
     }
 
+    pub fn set_outline_thickness(&mut self, thickness: f32) {
+        self.outline_thickness = thickness;
+    }
+
+    pub fn set_restitution(&mut self, restitution: f32) {
+        self.restitution = restitution;
+    }
+
     pub fn set_radius(&mut self, radius: f32) {
-        self.shape.set_radius(radius);
-        self.shape.set_origin((radius, radius));
+        self.radius = radius;
     }
 
     pub fn radius(&self) -> f32 {
-        self.shape.radius()
+        self.radius
+    }
+
+    pub fn outline_thickness(&self) -> f32 {
+        self.outline_thickness
+    }
+
+    /// Opens the ring at `gaps`, which may overlap. Only takes effect on the
+    /// collider if set before the ring is inserted into physics.
+    pub fn set_gaps(&mut self, gaps: Vec<Gap>) {
+        self.gaps = gaps;
+    }
+
+    pub fn gaps(&self) -> &[Gap] {
+        &self.gaps
+    }
+
+    /// Makes the ring follow `motion` from where it is now. Only takes
+    /// effect if its body is kinematic.
+    pub fn set_motion(&mut self, motion: Motion) {
+        self.origin = self.position;
+        self.motion = Some(motion);
+    }
+
+    pub fn motion(&self) -> Option<&Motion> {
+        self.motion.as_ref()
+    }
+
+    /// Makes the ring break after `health` hits from balls, or never if
+    /// `None`.
+    pub fn set_health(&mut self, health: Option<u32>) {
+        self.health = health;
+    }
+
+    /// Hits from balls left before the ring breaks, if it breaks at all.
+    pub fn health(&self) -> Option<u32> {
+        self.health
+    }
+
+    /// Makes the ring break once a ball gets out of it.
+    pub fn set_break_on_escape(&mut self, break_on_escape: bool) {
+        self.break_on_escape = break_on_escape;
     }
 
+    pub fn breaks_on_escape(&self) -> bool {
+        self.break_on_escape
+    }
+
+    /// Counts a hit from a ball against the ring's health. Returns whether
+    /// that was the last one it could take.
+    pub fn hit(&mut self) -> bool {
+        if self.is_broken() {
+            return false;
+        }
+        match &mut self.health {
+            Some(health) if *health > 0 => {
+                *health -= 1;
+                *health == 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Takes the ring out of physics and lets its pieces fly apart. Returns
+    /// the body it had, if it still had one.
+    pub fn shatter(&mut self, physics: &mut Physics) -> Option<RigidBodyHandle> {
+        self.shatter.get_or_insert_with(Shatter::default);
+        let rb_handle = self.rb_handle.take()?;
+        physics.remove_body(rb_handle);
+        Some(rb_handle)
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.shatter.is_some()
+    }
+
+    /// How far the ring turned since it was placed, in radians clockwise on
+    /// screen.
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    /// The parts of the ring between its gaps, as `(start, length)` in the
+    /// same angles as the gaps, or one full turn if it has none. These turn
+    /// with the ring.
+    pub fn solid_arcs(&self) -> Vec<(f32, f32)> {
+        if self.gaps.is_empty() {
+            return vec![(0.0, TAU)];
+        }
+        let mut gaps: Vec<(f32, f32)> = self
+            .gaps
+            .iter()
+            .map(|gap| {
+                let start = gap.start.rem_euclid(TAU);
+                (start, start + gap.length)
+            })
+            .collect();
+        gaps.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut merged: Vec<(f32, f32)> = Vec::with_capacity(gaps.len());
+        for (start, end) in gaps {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+
+        // The last gap may reach past a full turn, over the first ones.
+        let wrapped_end = merged.last().map_or(0.0, |last| last.1 - TAU);
+        let mut arcs = Vec::with_capacity(merged.len());
+        for (i, &(_, end)) in merged.iter().enumerate() {
+            let next_start = match merged.get(i + 1) {
+                Some(next) => next.0,
+                None => merged[0].0 + TAU,
+            };
+            let start = if next_start <= TAU {
+                end.max(wrapped_end)
+            } else {
+                end
+            };
+            if next_start > start {
+                arcs.push((start, next_start - start));
+            }
+        }
+        arcs
+    }
+
+    pub fn position(&self) -> Vector<Real> {
+        self.position
+    }
+
+    /// The ring's collider, a strip of triangles around it with the gaps
+    /// left out.
     pub fn create_collider(&self) -> Collider {
-        let (radius, thickness, point_count) = {
-            (
-                self.shape.radius(),
-                self.shape.outline_thickness(),
-                self.shape.point_count(),
-            )
-        };
-        let (vertices, indices) = {
+        let (radius, thickness, point_count) =
+            (self.radius, self.outline_thickness, self.point_count);
+        let (vertices, indices) = if !self.gaps.is_empty() {
+            self.arc_strips()
+        } else {
             assert!(point_count > 2, "A ring must have at least 3 points.");
             assert!(radius > 0.0, "A ring must have a radius greater than 0.");
             assert!(
@@ -129,27 +341,109 @@ impl<'s> Ring<'s> {
             (vertices, indices)
         };
 
+        // The lower threshold of the two colliders applies, so leave it to
+        // whatever hits the ring.
         ColliderBuilder::trimesh(vertices, indices)
-            .active_events(ActiveEvents::COLLISION_EVENTS)
-            .restitution(1.035)
+            .active_events(ActiveEvents::COLLISION_EVENTS | ActiveEvents::CONTACT_FORCE_EVENTS)
+            .contact_force_event_threshold(Real::MAX)
+            .restitution(self.restitution)
             .build()
     }
+
+    /// Triangle strips over the solid arcs, each split into about as many
+    /// segments as the same arc of a closed ring.
+    fn arc_strips(&self) -> (Vec<Point2<Real>>, Vec<[u32; 3]>) {
+        let outer = self.radius + self.outline_thickness;
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (start, length) in self.solid_arcs() {
+            let segments = ((length / TAU * self.point_count as f32).ceil() as u32).max(1);
+            let first = vertices.len() as u32;
+            for step in 0..=segments {
+                // The body is turned a quarter, so undo that here.
+                let angle = start + length * step as f32 / segments as f32 - PI / 2.0;
+                let (sin, cos) = angle.sin_cos();
+                vertices.push(Point2::new(cos * outer, sin * outer));
+                vertices.push(Point2::new(cos * self.radius, sin * self.radius));
+            }
+            for i in first..first + segments * 2 {
+                indices.push([i, i + 1, i + 2]);
+            }
+        }
+        (vertices, indices)
+    }
 }
 
-impl util::Drawable for Ring<'_> {
-    fn draw(
-        &mut self,
-        target: &mut dyn sfml::graphics::RenderTarget,
-        states: &sfml::graphics::RenderStates,
-    ) {
-        self.shape.draw(target, states);
+impl Drawable for Ring {
+    fn draw(&self, renderer: &mut dyn Renderer, alpha: f32) {
+        let center = self.previous_position.lerp(&self.position, alpha);
+        let angle = self.previous_angle + (self.angle - self.previous_angle) * alpha;
+        if let Some(shatter) = self.shatter {
+            let age = shatter.previous_age + (shatter.age - shatter.previous_age) * alpha;
+            self.draw_shards(renderer, center, angle, age);
+            return;
+        }
+        if !self.gaps.is_empty() {
+            for (start, length) in self.solid_arcs() {
+                renderer.draw_arc(&Arc {
+                    center,
+                    radius: self.radius,
+                    thickness: self.outline_thickness,
+                    start: start + angle,
+                    length,
+                    color: self.outline_color,
+                    point_count: self.point_count,
+                });
+            }
+            return;
+        }
+        renderer.draw_circle(&Circle {
+            center,
+            radius: self.radius,
+            outline_thickness: self.outline_thickness,
+            fill: Color::TRANSPARENT,
+            outline: self.outline_color,
+            point_count: self.point_count,
+        });
+    }
+}
+
+impl Ring {
+    /// The solid arcs cut into pieces, each `age` seconds along its way out
+    /// from the center and fading.
+    fn draw_shards(&self, renderer: &mut dyn Renderer, center: Vector<Real>, angle: f32, age: f32) {
+        if age >= SHATTER_SECONDS {
+            return;
+        }
+        let fade = 1.0 - age / SHATTER_SECONDS;
+        let color = Color {
+            a: (self.outline_color.a as f32 * fade) as u8,
+            ..self.outline_color
+        };
+        for (start, length) in self.solid_arcs() {
+            let pieces = (length / TAU * SHARDS_PER_TURN).ceil().max(1.0);
+            let piece = length / pieces;
+            for i in 0..pieces as usize {
+                let middle = start + angle + piece * (i as f32 + 0.5);
+                let direction = Vector::new(middle.cos(), middle.sin());
+                renderer.draw_arc(&Arc {
+                    center: center + direction * SHATTER_SPEED * age,
+                    radius: self.radius,
+                    thickness: self.outline_thickness * fade,
+                    start: middle - piece * 0.4,
+                    length: piece * 0.8,
+                    color,
+                    point_count: self.point_count,
+                });
+            }
+        }
     }
 }
 
-impl PhysicsObject for Ring<'_> {
+impl PhysicsObject for Ring {
     fn insert_into_physics(&mut self, rbtype: RigidBodyType, physics: &mut Physics) {
         let rb = RigidBodyBuilder::new(rbtype)
-            .translation(self.shape.position().to_na_mat2x1())
+            .translation(self.position)
             .rotation(PI / 2.0)
             .ccd_enabled(true)
             .build();
@@ -159,6 +453,8 @@ impl PhysicsObject for Ring<'_> {
     }
 }
 
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
 pub enum RingSize {
     Small,
     Medium,
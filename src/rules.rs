@@ -0,0 +1,551 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    f32::consts::{FRAC_1_SQRT_2, TAU},
+    rc::Rc,
+    str::FromStr,
+};
+
+use rand::{rngs::StdRng, Rng};
+use rapier2d::{
+    math::{Real, Rotation, Vector},
+    prelude::RigidBodyHandle,
+};
+use serde::Deserialize;
+
+use crate::{
+    ball::Ball, entity::Entity, impact::Impact, physics::Physics, render::Color, ring::Ring,
+};
+
+/// How an object reacts when a collision it took part in ends.
+///
+/// Rules are attached per object or per pair of objects in a [`RuleSet`] and
+/// run once for every object they apply to, in the order they were listed.
+pub trait CollisionRule {
+    fn apply(&self, ctx: &mut RuleContext<'_>);
+}
+
+/// The rules run when nothing more specific was configured.
+pub type Rules = Rc<[Box<dyn CollisionRule>]>;
+
+/// What a [`CollisionRule`] gets to look at and change: the object it is
+/// applied to, the object it collided with, how hard they hit, and ways to
+/// spawn or merge balls once the current step is done.
+pub struct RuleContext<'a> {
+    pub target: Entity,
+    pub other: Entity,
+    /// The collision, with the normal pointing from `target` to `other`.
+    pub impact: Impact,
+    pub(crate) physics: &'a mut Physics,
+    pub(crate) balls: &'a mut [Ball],
+    pub(crate) rings: &'a mut [Ring],
+    pub(crate) rng: &'a mut StdRng,
+    pub(crate) spawns: &'a mut Vec<Spawn>,
+    pub(crate) merges: &'a mut Vec<[usize; 2]>,
+    pub(crate) max_balls: Option<usize>,
+}
+
+/// A ball waiting to be inserted after the step that asked for it, taking
+/// over the rules of the ball it came from.
+pub(crate) struct Spawn {
+    pub ball: Ball,
+    pub parent: usize,
+}
+
+impl RuleContext<'_> {
+    pub fn rng(&mut self) -> &mut StdRng {
+        self.rng
+    }
+
+    pub fn radius(&self) -> f32 {
+        match self.target {
+            Entity::Ball(i) => self.balls[i].radius(),
+            Entity::Ring(i) => self.rings[i].radius(),
+        }
+    }
+
+    pub fn position(&self) -> Vector<Real> {
+        match self.target {
+            Entity::Ball(i) => self.balls[i].position(),
+            Entity::Ring(i) => self.rings[i].position(),
+        }
+    }
+
+    pub fn velocity(&self) -> Vector<Real> {
+        self.physics
+            .rigidbody_set
+            .get(self.rb_handle())
+            .map_or(Vector::zeros(), |rb| *rb.linvel())
+    }
+
+    /// Resizes the target and rebuilds its collider to match.
+    pub fn set_radius(&mut self, radius: f32) {
+        let handle = self.rb_handle();
+        let collider = match self.target {
+            Entity::Ball(i) => {
+                self.balls[i].set_radius(radius);
+                self.balls[i].create_collider(self.physics.gravity())
+            }
+            Entity::Ring(i) => {
+                self.rings[i].set_radius(radius);
+                self.rings[i].create_collider()
+            }
+        };
+        self.physics.set_collider(handle, collider);
+    }
+
+    pub fn set_outline_color(&mut self, color: Color) {
+        match self.target {
+            Entity::Ball(i) => self.balls[i].set_outline_color(color),
+            Entity::Ring(i) => self.rings[i].set_outline_color(color),
+        }
+    }
+
+    /// Gives the target a random bright outline color.
+    pub fn rand_outline_color(&mut self) {
+        match self.target {
+            Entity::Ball(i) => self.balls[i].rand_outline_color(self.rng),
+            Entity::Ring(i) => self.rings[i].rand_outline_color(self.rng),
+        }
+    }
+
+    pub fn set_restitution(&mut self, restitution: f32) {
+        let handle = self.rb_handle();
+        let collider = match self.target {
+            Entity::Ball(i) => {
+                self.balls[i].set_restitution(restitution);
+                self.balls[i].create_collider(self.physics.gravity())
+            }
+            Entity::Ring(i) => {
+                self.rings[i].set_restitution(restitution);
+                self.rings[i].create_collider()
+            }
+        };
+        self.physics.set_collider(handle, collider);
+    }
+
+    /// Moves the target's body without changing its velocity.
+    pub fn set_position(&mut self, position: Vector<Real>) {
+        if let Some(rb) = self.physics.rigidbody_set.get_mut(self.rb_handle()) {
+            rb.set_translation(position, true);
+        }
+    }
+
+    pub fn set_velocity(&mut self, velocity: Vector<Real>) {
+        if let Some(rb) = self.physics.rigidbody_set.get_mut(self.rb_handle()) {
+            rb.set_linvel(velocity, true);
+        }
+    }
+
+    /// The ball in the collision: the target if it is one, or else the other
+    /// object if that is.
+    pub fn ball(&self) -> Option<usize> {
+        match (self.target, self.other) {
+            (Entity::Ball(i), _) | (_, Entity::Ball(i)) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// The ring in the collision, if there is one.
+    pub fn ring(&self) -> Option<usize> {
+        match (self.target, self.other) {
+            (Entity::Ring(i), _) | (_, Entity::Ring(i)) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Whether a ball spawned now would fit under the scene's cap on balls,
+    /// counting those already queued during the step.
+    pub fn can_spawn(&self) -> bool {
+        let Some(max) = self.max_balls else {
+            return true;
+        };
+        let live = self.balls.iter().filter(|ball| !ball.is_absorbed()).count();
+        live + self.spawns.len() < max
+    }
+
+    /// Queues `ball` to be added as a dynamic body once the current step's
+    /// collisions have all been handled. Does nothing unless a ball took part
+    /// in the collision, whose rules the new one inherits, or when there is
+    /// no room for it.
+    pub fn spawn_ball(&mut self, ball: Ball) {
+        if !self.can_spawn() {
+            return;
+        }
+        if let Some(parent) = self.ball() {
+            self.spawns.push(Spawn { ball, parent });
+        }
+    }
+
+    /// Queues the target ball and the ball it hit to merge into one once the
+    /// current step's collisions have all been handled. The bigger one, or
+    /// with equal sizes the target, takes in the other. Does nothing unless
+    /// both are balls.
+    pub fn merge_balls(&mut self) {
+        let (Entity::Ball(target), Entity::Ball(other)) = (self.target, self.other) else {
+            return;
+        };
+        let pair = if self.balls[other].radius() > self.balls[target].radius() {
+            [other, target]
+        } else {
+            [target, other]
+        };
+        self.merges.push(pair);
+    }
+
+    /// A new ball looking like the one in the collision, for rules that
+    /// spawn copies.
+    pub fn clone_target_ball<P: Into<Vector<Real>>>(&self, position: P, radius: f32) -> Ball {
+        let mut ball = Ball::new_with_radius(position, radius);
+        if let Some(i) = self.ball() {
+            let parent = &self.balls[i];
+            ball.set_outline_thickness(parent.outline_thickness());
+            ball.set_outline_color(parent.outline_color());
+            ball.set_restitution(parent.restitution());
+        }
+        ball
+    }
+
+    fn rb_handle(&self) -> RigidBodyHandle {
+        match self.target {
+            Entity::Ball(i) => self.balls[i].rb_handle(),
+            Entity::Ring(i) => self.rings[i].rb_handle(),
+        }
+        .expect("objects in a simulation are always in physics")
+    }
+}
+
+/// Multiplies the target's radius by `factor`, or with `by_impact` by
+/// `factor` once for every average bounce's worth of impact strength, up to
+/// [`MAX_IMPACT_GROWTH`] times.
+pub struct Grow {
+    pub factor: f32,
+    pub by_impact: bool,
+}
+
+/// Most times a single hit applies an impact-scaled [`Grow`].
+pub const MAX_IMPACT_GROWTH: f32 = 4.0;
+
+impl CollisionRule for Grow {
+    fn apply(&self, ctx: &mut RuleContext<'_>) {
+        let factor = if self.by_impact {
+            let times = ctx.impact.relative_strength().min(MAX_IMPACT_GROWTH);
+            self.factor.powf(times)
+        } else {
+            self.factor
+        };
+        ctx.set_radius(ctx.radius() * factor);
+    }
+}
+
+/// Divides the target's radius by `factor`, down to `min_radius`.
+pub struct Shrink {
+    pub factor: f32,
+    pub min_radius: f32,
+}
+
+impl CollisionRule for Shrink {
+    fn apply(&self, ctx: &mut RuleContext<'_>) {
+        let radius = (ctx.radius() / self.factor).max(self.min_radius);
+        ctx.set_radius(radius);
+    }
+}
+
+/// Picks a new outline color from `palette`, or a random bright one when
+/// the palette is empty.
+pub struct Recolor {
+    pub palette: Vec<Color>,
+}
+
+impl CollisionRule for Recolor {
+    fn apply(&self, ctx: &mut RuleContext<'_>) {
+        if self.palette.is_empty() {
+            ctx.rand_outline_color();
+        } else {
+            let color = self.palette[ctx.rng().gen_range(0..self.palette.len())];
+            ctx.set_outline_color(color);
+        }
+    }
+}
+
+/// Sets how bouncy the target is from now on.
+pub struct SetRestitution {
+    pub restitution: f32,
+}
+
+impl CollisionRule for SetRestitution {
+    fn apply(&self, ctx: &mut RuleContext<'_>) {
+        ctx.set_restitution(self.restitution);
+    }
+}
+
+/// Splits a ball into two of half its area each, side by side along the
+/// contact surface and set back from it as far as a half reaches, so
+/// neither ends up in or past what the ball hit. Their velocities spread a
+/// little apart. Balls no bigger than `threshold`, that would end up smaller
+/// than `min_radius`, or whose other half would not fit under the cap on
+/// balls, are left alone.
+pub struct Split {
+    pub min_radius: f32,
+    pub threshold: f32,
+}
+
+impl CollisionRule for Split {
+    fn apply(&self, ctx: &mut RuleContext<'_>) {
+        let Entity::Ball(i) = ctx.target else {
+            return;
+        };
+        if ctx.radius() <= self.threshold {
+            return;
+        }
+        let radius = ctx.radius() * FRAC_1_SQRT_2;
+        if radius < self.min_radius || !ctx.can_spawn() {
+            return;
+        }
+        let (position, velocity) = (ctx.position(), ctx.velocity());
+        let normal = ctx.impact.normal;
+        let tangent = Vector::new(-normal.y, normal.x);
+        let reach = radius + ctx.balls[i].outline_thickness();
+        let setback = position - normal * reach;
+        let spread = Rotation::new(0.26);
+
+        ctx.set_radius(radius);
+        ctx.set_position(setback - tangent * reach);
+        ctx.set_velocity(spread.inverse() * velocity);
+        let mut half = ctx.clone_target_ball(setback + tangent * reach, radius);
+        half.set_velocity(spread * velocity);
+        ctx.spawn_ball(half);
+    }
+}
+
+/// Adds a copy of the target ball beside it along the contact surface, on
+/// the side it isn't heading towards and set back from the surface so it
+/// clears a curved one, with its velocity mirrored in that surface: where
+/// the ball bounced off, the copy heads in.
+pub struct Duplicate;
+
+impl CollisionRule for Duplicate {
+    fn apply(&self, ctx: &mut RuleContext<'_>) {
+        let Entity::Ball(i) = ctx.target else {
+            return;
+        };
+        let normal = ctx.impact.normal;
+        let velocity = ctx.velocity();
+        let tangent = Vector::new(-normal.y, normal.x);
+        let side = if velocity.dot(&tangent) > 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let reach = ctx.radius() + ctx.balls[i].outline_thickness();
+        let position = ctx.position() + (tangent * (side * 2.0) - normal) * reach;
+        let mut copy = ctx.clone_target_ball(position, ctx.radius());
+        copy.set_velocity(velocity - normal * (2.0 * velocity.dot(&normal)));
+        ctx.spawn_ball(copy);
+    }
+}
+
+/// Every `every`th time the same target is hit, adds a copy of the ball
+/// that took part at the center of the ring that did, heading off in a
+/// random direction as fast as that ball. Nothing spawns from collisions
+/// between two balls, but they still count.
+pub struct SpawnEvery {
+    pub every: u32,
+    hits: RefCell<HashMap<Entity, u32>>,
+}
+
+impl SpawnEvery {
+    pub fn new(every: u32) -> Self {
+        Self {
+            every,
+            hits: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl CollisionRule for SpawnEvery {
+    fn apply(&self, ctx: &mut RuleContext<'_>) {
+        let mut hits = self.hits.borrow_mut();
+        let count = hits.entry(ctx.target).or_insert(0);
+        *count += 1;
+        if *count % self.every != 0 {
+            return;
+        }
+        let (Some(ball), Some(ring)) = (ctx.ball(), ctx.ring()) else {
+            return;
+        };
+        let center = ctx.rings[ring].position();
+        let speed = ctx
+            .physics
+            .rigidbody_set
+            .get(ctx.balls[ball].rb_handle().expect("balls are in physics"))
+            .map_or(0.0, |rb| rb.linvel().norm());
+        let direction = Rotation::new(ctx.rng().gen_range(0.0..TAU)) * Vector::x();
+        let mut copy = ctx.clone_target_ball(center, ctx.balls[ball].radius());
+        copy.set_velocity(direction * speed);
+        ctx.spawn_ball(copy);
+    }
+}
+
+/// Merges the target ball with the ball it hit into one as big as both
+/// together, keeping their momentum.
+pub struct Merge;
+
+impl CollisionRule for Merge {
+    fn apply(&self, ctx: &mut RuleContext<'_>) {
+        ctx.merge_balls();
+    }
+}
+
+/// Explicitly does nothing, to switch off the default reaction.
+pub struct Nothing;
+
+impl CollisionRule for Nothing {
+    fn apply(&self, _ctx: &mut RuleContext<'_>) {}
+}
+
+/// Which objects one side of a [`PairRules`] refers to, written `ball`,
+/// `ring`, `ball:N` or `ring:N` in scene files.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub enum Selector {
+    AnyBall,
+    AnyRing,
+    Ball(usize),
+    Ring(usize),
+}
+
+impl Selector {
+    pub fn matches(self, entity: Entity) -> bool {
+        match (self, entity) {
+            (Selector::AnyBall, Entity::Ball(_)) | (Selector::AnyRing, Entity::Ring(_)) => true,
+            (Selector::Ball(a), Entity::Ball(b)) | (Selector::Ring(a), Entity::Ring(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, index) = match s.split_once(':') {
+            Some((kind, index)) => match index.parse() {
+                Ok(index) => (kind, Some(index)),
+                Err(_) => return Err(format!("invalid index in selector `{s}`")),
+            },
+            None => (s, None),
+        };
+        match (kind, index) {
+            ("ball", None) => Ok(Selector::AnyBall),
+            ("ring", None) => Ok(Selector::AnyRing),
+            ("ball", Some(index)) => Ok(Selector::Ball(index)),
+            ("ring", Some(index)) => Ok(Selector::Ring(index)),
+            _ => Err(format!(
+                "unknown selector `{s}`, expected `ball`, `ring`, `ball:N` or `ring:N`"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Selector {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Side of a [`PairRules`] its rules run for.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApplyTo {
+    First,
+    Second,
+    Both,
+}
+
+/// Rules for collisions between two kinds of objects, used instead of the
+/// own rules of the side(s) picked by `apply_to`. The other side reacts as it
+/// normally would.
+pub struct PairRules {
+    pub between: [Selector; 2],
+    pub apply_to: ApplyTo,
+    pub rules: Rules,
+}
+
+/// Every rule in a simulation: one list per object and the pair overrides.
+#[derive(Default)]
+pub struct RuleSet {
+    balls: Vec<Rules>,
+    rings: Vec<Rules>,
+    pairs: Vec<PairRules>,
+}
+
+impl RuleSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, entity: Entity, rules: Rules) {
+        let (list, i) = match entity {
+            Entity::Ball(i) => (&mut self.balls, i),
+            Entity::Ring(i) => (&mut self.rings, i),
+        };
+        if list.len() <= i {
+            list.resize_with(i + 1, || Rc::from(Vec::new()));
+        }
+        list[i] = rules;
+    }
+
+    pub fn get(&self, entity: Entity) -> Rules {
+        let rules = match entity {
+            Entity::Ball(i) => self.balls.get(i),
+            Entity::Ring(i) => self.rings.get(i),
+        };
+        rules.cloned().unwrap_or_else(|| Rc::from(Vec::new()))
+    }
+
+    /// Later pairs take precedence over earlier ones.
+    pub fn add_pair(&mut self, pair: PairRules) {
+        self.pairs.push(pair);
+    }
+
+    /// The rules to run on `target` after it collided with `other`.
+    pub fn rules_for(&self, target: Entity, other: Entity) -> Rules {
+        for pair in self.pairs.iter().rev() {
+            let [a, b] = pair.between;
+            let as_first = a.matches(target) && b.matches(other);
+            let as_second = b.matches(target) && a.matches(other);
+            let applies = match pair.apply_to {
+                ApplyTo::First => as_first,
+                ApplyTo::Second => as_second,
+                ApplyTo::Both => as_first || as_second,
+            };
+            if applies {
+                return pair.rules.clone();
+            }
+        }
+        self.get(target)
+    }
+}
+
+/// What balls did before rules were configurable: take a random color and
+/// grow by 1%.
+pub fn default_ball_rules() -> Rules {
+    Rc::from(vec![
+        Box::new(Recolor {
+            palette: Vec::new(),
+        }) as Box<dyn CollisionRule>,
+        Box::new(Grow {
+            factor: 1.01,
+            by_impact: false,
+        }),
+    ])
+}
+
+pub fn default_ring_rules() -> Rules {
+    Rc::from(vec![Box::new(Recolor {
+        palette: Vec::new(),
+    }) as Box<dyn CollisionRule>])
+}
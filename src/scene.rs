@@ -0,0 +1,966 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use rapier2d::dynamics::RigidBodyType;
+use serde::Deserialize;
+
+use crate::{
+    ball::{Ball, BallSize},
+    ending::EndCondition,
+    entity::Entity,
+    melody::{Melody, MelodyEnd, MelodyError},
+    motion::{Keyframe, Motion, Pose},
+    music::Tuning,
+    physics::DEFAULT_GRAVITY,
+    polyphony::Polyphony,
+    render::Color,
+    ring::{Gap, Ring, RingSize},
+    rules::{self, ApplyTo, CollisionRule, PairRules, RuleSet, Rules, Selector},
+    sounds::{SoundBank, SoundType},
+    synth::Voices,
+};
+
+/// Everything needed to set up a run: window size, gravity and the objects
+/// placed in the world, as read from a TOML scene file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    /// Seed for every random choice made during the run, unless one is given
+    /// on the command line.
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub window: WindowDesc,
+    #[serde(default = "default_gravity")]
+    pub gravity: [f32; 2],
+    /// Physics steps per simulated second, independent of the display's
+    /// refresh rate.
+    #[serde(default = "default_physics_rate")]
+    pub physics_rate: f32,
+    /// Snaps bounce pitches to a musical scale when set.
+    pub music: Option<Tuning>,
+    /// Plays a MIDI melody one note per collision when set, instead of
+    /// pitching bounces by speed.
+    pub melody: Option<MelodyDesc>,
+    /// Synthesized sounds for balls and rings, played instead of the bounce
+    /// sample.
+    #[serde(default, rename = "voice")]
+    pub voices: Voices,
+    /// How many sounds may play at once, and how often a ball may sound.
+    #[serde(default)]
+    pub polyphony: Polyphony,
+    #[serde(default)]
+    pub sounds: SoundsDesc,
+    /// Most balls there may be at once; rules spawn no more past it.
+    pub max_balls: Option<usize>,
+    #[serde(default, rename = "ball")]
+    pub balls: Vec<BallDesc>,
+    #[serde(default, rename = "ring")]
+    pub rings: Vec<RingDesc>,
+    /// Rings generated around one center, added after the listed ones once
+    /// the scene is parsed.
+    pub concentric: Option<ConcentricDesc>,
+    #[serde(default, rename = "pair")]
+    pub pairs: Vec<PairDesc>,
+    /// What ends the run, checked in order after every step.
+    #[serde(default, rename = "end")]
+    pub end_conditions: Vec<EndCondition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowDesc {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BallDesc {
+    pub position: [f32; 2],
+    pub radius: Option<f32>,
+    pub size: Option<BallSize>,
+    #[serde(default = "default_thickness")]
+    pub outline_thickness: f32,
+    #[serde(default = "default_color")]
+    pub color: [u8; 3],
+    #[serde(default)]
+    pub velocity: [f32; 2],
+    #[serde(default = "default_restitution")]
+    pub restitution: f32,
+    #[serde(default = "default_ball_body")]
+    pub body: BodyType,
+    /// How the ball reacts to collisions. Left out, it takes a random color
+    /// and grows by 1%.
+    pub rules: Option<Vec<RuleDesc>>,
+    /// Sample from the sound bank played when the ball hits something.
+    pub sound: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RingDesc {
+    pub position: [f32; 2],
+    pub radius: Option<f32>,
+    pub size: Option<RingSize>,
+    #[serde(default = "default_thickness")]
+    pub outline_thickness: f32,
+    #[serde(default = "default_color")]
+    pub color: [u8; 3],
+    #[serde(default = "default_restitution")]
+    pub restitution: f32,
+    /// Left out, kinematic if the ring has a motion and fixed otherwise.
+    pub body: Option<BodyType>,
+    /// How the ring reacts to collisions. Left out, it takes a random color.
+    pub rules: Option<Vec<RuleDesc>>,
+    /// Sample from the sound bank played when a ball without a sound of its
+    /// own hits the ring.
+    pub sound: Option<String>,
+    /// Openings balls can leave the ring through.
+    #[serde(default, rename = "gap")]
+    pub gaps: Vec<GapDesc>,
+    pub motion: Option<MotionDesc>,
+    /// Hits from balls the ring takes before it breaks. Left out, it never
+    /// breaks from being hit.
+    pub health: Option<u32>,
+    /// Break the ring once a ball gets out of it.
+    #[serde(default)]
+    pub break_on_escape: bool,
+}
+
+/// `count` rings around `position`, from `inner_radius` outwards every
+/// `spacing` pixels. Angles are in degrees and speeds in degrees per second.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConcentricDesc {
+    pub position: [f32; 2],
+    pub count: usize,
+    pub inner_radius: f32,
+    pub spacing: f32,
+    #[serde(default = "default_thickness")]
+    pub outline_thickness: f32,
+    /// Colors of the rings from the inside out, repeating if there are more
+    /// rings than colors.
+    #[serde(default)]
+    pub colors: Vec<[u8; 3]>,
+    #[serde(default = "default_restitution")]
+    pub restitution: f32,
+    /// Width of an opening in every ring. Left at 0, they are closed.
+    #[serde(default)]
+    pub gap: f32,
+    /// Where the innermost ring's opening starts.
+    #[serde(default)]
+    pub gap_start: f32,
+    /// How much further round each ring's opening is than the one inside it.
+    #[serde(default)]
+    pub gap_step: f32,
+    /// How fast the rings spin, if at all.
+    #[serde(default)]
+    pub spin: f32,
+    /// Spin every other ring the other way.
+    #[serde(default)]
+    pub alternate: bool,
+    pub health: Option<u32>,
+    #[serde(default)]
+    pub break_on_escape: bool,
+    pub rules: Option<Vec<RuleDesc>>,
+    pub sound: Option<String>,
+}
+
+/// An opening in a ring, in degrees going clockwise from the right.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct GapDesc {
+    pub start: f32,
+    pub length: f32,
+}
+
+/// How a kinematic ring moves, picked by its `kind`. Angles are in degrees
+/// clockwise and offsets in pixels, both from where the ring was placed.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum MotionDesc {
+    Spin {
+        /// Degrees per second.
+        speed: f32,
+    },
+    Oscillate {
+        #[serde(default)]
+        amplitude: f32,
+        #[serde(default)]
+        offset: [f32; 2],
+        period: f32,
+    },
+    Path {
+        keyframes: Vec<KeyframeDesc>,
+        #[serde(default)]
+        repeat: bool,
+    },
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct KeyframeDesc {
+    pub time: f32,
+    #[serde(default)]
+    pub offset: [f32; 2],
+    #[serde(default)]
+    pub angle: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MelodyDesc {
+    /// Standard MIDI file, relative to the scene file.
+    pub file: PathBuf,
+    #[serde(default)]
+    pub track: usize,
+    /// MIDI key the bounce sample sounds at unpitched.
+    #[serde(default = "default_sample_key")]
+    pub sample_key: u8,
+    #[serde(default = "default_melody_end")]
+    pub end: MelodyEnd,
+    /// Collisions slower than this are silent and don't advance the melody.
+    #[serde(default)]
+    pub min_speed: f32,
+}
+
+impl MelodyDesc {
+    pub fn load(&self) -> Result<Melody, MelodyError> {
+        let mut melody = Melody::from_midi(&self.file, self.track)?;
+        melody.set_sample_key(self.sample_key);
+        melody.set_end(self.end);
+        melody.set_min_speed(self.min_speed);
+        Ok(melody)
+    }
+}
+
+/// Where named sounds come from, and which collisions play which.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SoundsDesc {
+    /// Directory of the samples balls, rings and pairs refer to by name,
+    /// relative to the scene file.
+    #[serde(default = "default_sound_bank")]
+    pub bank: PathBuf,
+    #[serde(default, rename = "pair")]
+    pub pairs: Vec<PairSoundDesc>,
+}
+
+/// A sample played for collisions between `between[0]` and `between[1]`,
+/// instead of either object's own.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PairSoundDesc {
+    pub between: [Selector; 2],
+    pub sound: String,
+}
+
+/// Rules overriding the objects' own for collisions between `between[0]` and
+/// `between[1]`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PairDesc {
+    pub between: [Selector; 2],
+    #[serde(default = "default_apply_to")]
+    pub apply_to: ApplyTo,
+    pub rules: Vec<RuleDesc>,
+}
+
+/// One collision rule, picked by its `kind`.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum RuleDesc {
+    Grow {
+        #[serde(default = "default_grow_factor")]
+        factor: f32,
+        /// Grow more for harder hits and less for softer ones.
+        #[serde(default)]
+        by_impact: bool,
+    },
+    Shrink {
+        #[serde(default = "default_grow_factor")]
+        factor: f32,
+        #[serde(default = "default_min_radius")]
+        min_radius: f32,
+    },
+    Recolor {
+        #[serde(default)]
+        palette: Vec<[u8; 3]>,
+    },
+    Restitution {
+        value: f32,
+    },
+    Split {
+        min_radius: f32,
+        /// Only split balls bigger than this.
+        #[serde(default)]
+        threshold: f32,
+    },
+    /// Add a mirrored copy of the ball at the contact point.
+    Duplicate,
+    /// Add a copy of the ball at the ring's center every `every` hits.
+    Spawn {
+        every: u32,
+    },
+    /// Merge with the ball hit into one as big as both.
+    Merge,
+    None,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyType {
+    Dynamic,
+    Fixed,
+    Kinematic,
+}
+
+impl From<BodyType> for RigidBodyType {
+    fn from(body: BodyType) -> Self {
+        match body {
+            BodyType::Dynamic => RigidBodyType::Dynamic,
+            BodyType::Fixed => RigidBodyType::Fixed,
+            BodyType::Kinematic => RigidBodyType::KinematicPositionBased,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    Parse {
+        path: String,
+        source: toml::de::Error,
+    },
+    Invalid {
+        path: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "failed to read scene {path}: {source}"),
+            Self::Parse { path, source } => write!(f, "failed to parse scene {path}: {source}"),
+            Self::Invalid { path, message } => write!(f, "invalid scene {path}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { source, .. } => Some(source),
+            Self::Invalid { .. } => None,
+        }
+    }
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        let dir = path.as_ref().parent().unwrap_or(Path::new(""));
+        let path = path.as_ref().display().to_string();
+        match fs::read_to_string(&path) {
+            Ok(source) => Self::parse(&source, path, dir),
+            Err(source) => Err(SceneError::Io { path, source }),
+        }
+    }
+
+    /// Parses a scene from TOML source that did not come from a file. Paths
+    /// in it are relative to the working directory.
+    pub fn from_toml(source: &str) -> Result<Self, SceneError> {
+        Self::parse(source, String::from("<inline>"), Path::new(""))
+    }
+
+    /// Parses and checks a scene whose relative paths start from `dir`.
+    fn parse(source: &str, path: String, dir: &Path) -> Result<Self, SceneError> {
+        let mut scene: Scene = match toml::from_str(source) {
+            Ok(scene) => scene,
+            Err(source) => return Err(SceneError::Parse { path, source }),
+        };
+        if let Some(melody) = &mut scene.melody {
+            melody.file = dir.join(&melody.file);
+        }
+        scene.sounds.bank = dir.join(&scene.sounds.bank);
+        let checked = scene
+            .generate_rings()
+            .and_then(|()| scene.validate())
+            .and_then(|()| scene.check_sound_files());
+        match checked {
+            Ok(()) => Ok(scene),
+            Err(message) => Err(SceneError::Invalid { path, message }),
+        }
+    }
+
+    /// Adds the concentric rings, if any, to the listed ones.
+    fn generate_rings(&mut self) -> Result<(), String> {
+        let Some(concentric) = self.concentric.take() else {
+            return Ok(());
+        };
+        concentric
+            .validate()
+            .map_err(|message| format!("concentric: {message}"))?;
+        self.rings.extend(concentric.rings());
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.window.width == 0 || self.window.height == 0 {
+            return Err(String::from("window size must be greater than 0"));
+        }
+        if self.physics_rate <= 0.0 || !self.physics_rate.is_finite() {
+            return Err(String::from("physics_rate must be greater than 0"));
+        }
+        for (name, voice) in [("ball", &self.voices.ball), ("ring", &self.voices.ring)] {
+            if let Some(voice) = voice {
+                voice
+                    .validate()
+                    .map_err(|message| format!("voice.{name}: {message}"))?;
+            }
+        }
+        if let Some(music) = &self.music {
+            music
+                .validate()
+                .map_err(|message| format!("music: {message}"))?;
+        }
+        self.polyphony
+            .validate()
+            .and_then(|()| self.check_polyphony_names())
+            .map_err(|message| format!("polyphony: {message}"))?;
+        for (i, ball) in self.balls.iter().enumerate() {
+            check_shape(ball.radius, ball.size.is_some(), ball.outline_thickness)
+                .map_err(|message| format!("ball {i}: {message}"))?;
+            check_rules(ball.rules.iter().flatten())
+                .map_err(|message| format!("ball {i}: {message}"))?;
+        }
+        for (i, ring) in self.rings.iter().enumerate() {
+            check_shape(ring.radius, ring.size.is_some(), ring.outline_thickness)
+                .map_err(|message| format!("ring {i}: {message}"))?;
+            check_rules(ring.rules.iter().flatten())
+                .map_err(|message| format!("ring {i}: {message}"))?;
+            check_gaps(&ring.gaps).map_err(|message| format!("ring {i}: {message}"))?;
+            ring.check_motion()
+                .map_err(|message| format!("ring {i}: {message}"))?;
+            if ring.health == Some(0) {
+                return Err(format!("ring {i}: health must be greater than 0"));
+            }
+        }
+        for (i, pair) in self.pairs.iter().enumerate() {
+            self.check_selectors(pair.between)
+                .map_err(|message| format!("pair {i}: {message}"))?;
+            check_rules(&pair.rules).map_err(|message| format!("pair {i}: {message}"))?;
+        }
+        for (i, pair) in self.sounds.pairs.iter().enumerate() {
+            self.check_selectors(pair.between)
+                .map_err(|message| format!("sound pair {i}: {message}"))?;
+        }
+        for (i, condition) in self.end_conditions.iter().enumerate() {
+            condition
+                .validate()
+                .map_err(|message| format!("end {i}: {message}"))?;
+        }
+        Ok(())
+    }
+
+    fn check_selectors(&self, selectors: [Selector; 2]) -> Result<(), String> {
+        for selector in selectors {
+            let (kind, index, count) = match selector {
+                Selector::Ball(index) => ("ball", index, self.balls.len()),
+                Selector::Ring(index) => ("ring", index, self.rings.len()),
+                Selector::AnyBall | Selector::AnyRing => continue,
+            };
+            if index >= count {
+                return Err(format!("there is no {kind} {index}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes sure polyphony limits and priorities only name sounds the scene
+    /// has: built-in ones or those in its bank.
+    fn check_polyphony_names(&self) -> Result<(), String> {
+        let limited = self
+            .polyphony
+            .limits
+            .keys()
+            .chain(self.polyphony.priority.keys());
+        for name in limited {
+            let built_in = SoundType::ALL
+                .iter()
+                .any(|sound| sound.name() == Some(name));
+            if !built_in && !self.bank_sounds().any(|bank_sound| bank_sound == name) {
+                return Err(format!("there is no sound `{name}`"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Names of the bank samples the objects and pairs in the scene play.
+    fn bank_sounds(&self) -> impl Iterator<Item = &String> {
+        self.balls
+            .iter()
+            .map(|ball| &ball.sound)
+            .chain(self.rings.iter().map(|ring| &ring.sound))
+            .flatten()
+            .chain(self.sounds.pairs.iter().map(|pair| &pair.sound))
+    }
+
+    /// Makes sure every named sound has a sample in the bank.
+    fn check_sound_files(&self) -> Result<(), String> {
+        let bank = SoundBank::new(&self.sounds.bank);
+        for name in self.bank_sounds() {
+            let path = bank.path(name);
+            if !path.is_file() {
+                return Err(format!("sound `{name}` not found at {}", path.display()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The rules of every object in the scene and the pair overrides, indexed
+    /// the same way as the objects in a [`Simulation`](crate::sim::Simulation).
+    pub fn rule_set(&self) -> RuleSet {
+        let mut rule_set = RuleSet::new();
+        for (i, ball) in self.balls.iter().enumerate() {
+            let rules = ball
+                .rules
+                .as_deref()
+                .map_or_else(rules::default_ball_rules, build_rules);
+            rule_set.set(Entity::Ball(i), rules);
+        }
+        for (i, ring) in self.rings.iter().enumerate() {
+            let rules = ring
+                .rules
+                .as_deref()
+                .map_or_else(rules::default_ring_rules, build_rules);
+            rule_set.set(Entity::Ring(i), rules);
+        }
+        for pair in &self.pairs {
+            rule_set.add_pair(PairRules {
+                between: pair.between,
+                apply_to: pair.apply_to,
+                rules: build_rules(&pair.rules),
+            });
+        }
+        rule_set
+    }
+}
+
+fn check_rules<'a>(rules: impl IntoIterator<Item = &'a RuleDesc>) -> Result<(), String> {
+    for rule in rules {
+        match *rule {
+            RuleDesc::Grow { factor, .. } | RuleDesc::Shrink { factor, .. }
+                if factor <= 0.0 || !factor.is_finite() =>
+            {
+                return Err(String::from("rule factor must be greater than 0"))
+            }
+            RuleDesc::Shrink { min_radius, .. } | RuleDesc::Split { min_radius, .. }
+                if min_radius <= 0.0 || !min_radius.is_finite() =>
+            {
+                return Err(String::from("rule min_radius must be greater than 0"))
+            }
+            RuleDesc::Split { threshold, .. } if threshold < 0.0 || !threshold.is_finite() => {
+                return Err(String::from("split threshold must not be negative"))
+            }
+            RuleDesc::Restitution { value } if value < 0.0 || !value.is_finite() => {
+                return Err(String::from("restitution must not be negative"))
+            }
+            RuleDesc::Spawn { every: 0 } => {
+                return Err(String::from("spawn rule needs `every` greater than 0"))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn build_rules(rules: &[RuleDesc]) -> Rules {
+    rules.iter().map(RuleDesc::build).collect::<Vec<_>>().into()
+}
+
+impl RuleDesc {
+    pub fn build(&self) -> Box<dyn CollisionRule> {
+        match self {
+            RuleDesc::Grow { factor, by_impact } => Box::new(rules::Grow {
+                factor: *factor,
+                by_impact: *by_impact,
+            }),
+            RuleDesc::Shrink { factor, min_radius } => Box::new(rules::Shrink {
+                factor: *factor,
+                min_radius: *min_radius,
+            }),
+            RuleDesc::Recolor { palette } => Box::new(rules::Recolor {
+                palette: palette.iter().copied().map(to_color).collect(),
+            }),
+            RuleDesc::Restitution { value } => Box::new(rules::SetRestitution {
+                restitution: *value,
+            }),
+            RuleDesc::Split {
+                min_radius,
+                threshold,
+            } => Box::new(rules::Split {
+                min_radius: *min_radius,
+                threshold: *threshold,
+            }),
+            RuleDesc::Duplicate => Box::new(rules::Duplicate),
+            RuleDesc::Spawn { every } => Box::new(rules::SpawnEvery::new(*every)),
+            RuleDesc::Merge => Box::new(rules::Merge),
+            RuleDesc::None => Box::new(rules::Nothing),
+        }
+    }
+}
+
+fn check_shape(radius: Option<f32>, has_size: bool, thickness: f32) -> Result<(), String> {
+    match (radius, has_size) {
+        (Some(_), true) => return Err(String::from("set either `radius` or `size`, not both")),
+        (None, false) => return Err(String::from("one of `radius` or `size` is required")),
+        (Some(radius), false) if radius <= 0.0 || !radius.is_finite() => {
+            return Err(String::from("radius must be greater than 0"))
+        }
+        _ => {}
+    }
+    if thickness <= 0.0 || !thickness.is_finite() {
+        return Err(String::from("outline_thickness must be greater than 0"));
+    }
+    Ok(())
+}
+
+fn check_gaps(gaps: &[GapDesc]) -> Result<(), String> {
+    for (i, gap) in gaps.iter().enumerate() {
+        if !(gap.length > 0.0 && gap.length < 360.0) {
+            return Err(format!("gap {i}: length must be between 0 and 360 degrees"));
+        }
+    }
+    if gaps.iter().map(|gap| gap.length).sum::<f32>() >= 360.0 {
+        return Err(String::from("gaps must leave some of the ring"));
+    }
+    Ok(())
+}
+
+impl BallDesc {
+    pub fn build(&self) -> Ball {
+        let mut ball = match (self.radius, self.size) {
+            (Some(radius), _) => Ball::new_with_radius(self.position, radius),
+            (None, Some(size)) => Ball::new_with_size(self.position, size),
+            (None, None) => Ball::new(self.position),
+        };
+        ball.set_outline_thickness(self.outline_thickness);
+        ball.set_outline_color(to_color(self.color));
+        ball.set_restitution(self.restitution);
+        ball.set_velocity(self.velocity);
+        ball
+    }
+}
+
+impl RingDesc {
+    pub fn body_type(&self) -> RigidBodyType {
+        match (self.body, &self.motion) {
+            (Some(body), _) => body.into(),
+            (None, Some(_)) => RigidBodyType::KinematicPositionBased,
+            (None, None) => RigidBodyType::Fixed,
+        }
+    }
+
+    fn check_motion(&self) -> Result<(), String> {
+        let Some(motion) = &self.motion else {
+            return Ok(());
+        };
+        if !matches!(self.body, None | Some(BodyType::Kinematic)) {
+            return Err(String::from("only a kinematic ring can have a motion"));
+        }
+        match motion {
+            MotionDesc::Spin { speed } => {
+                if !speed.is_finite() {
+                    return Err(String::from("motion speed must be finite"));
+                }
+            }
+            MotionDesc::Oscillate {
+                amplitude,
+                offset,
+                period,
+            } => {
+                if *period <= 0.0 || !period.is_finite() {
+                    return Err(String::from("motion period must be greater than 0"));
+                }
+                if !amplitude.is_finite() || !offset.iter().all(|x| x.is_finite()) {
+                    return Err(String::from("motion amplitude and offset must be finite"));
+                }
+            }
+            MotionDesc::Path { keyframes, .. } => {
+                if keyframes.is_empty() {
+                    return Err(String::from("a motion path needs keyframes"));
+                }
+                let mut previous = 0.0;
+                for (i, keyframe) in keyframes.iter().enumerate() {
+                    if !keyframe.time.is_finite()
+                        || keyframe.time <= previous && !(i == 0 && keyframe.time == 0.0)
+                    {
+                        return Err(format!(
+                            "keyframe {i}: time must be later than the one before"
+                        ));
+                    }
+                    if !keyframe.angle.is_finite() || !keyframe.offset.iter().all(|x| x.is_finite())
+                    {
+                        return Err(format!("keyframe {i}: offset and angle must be finite"));
+                    }
+                    previous = keyframe.time;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn build(&self) -> Ring {
+        let mut ring = match (self.radius, self.size) {
+            (Some(radius), _) => Ring::new_with_radius(self.position, radius),
+            (None, Some(size)) => Ring::new_with_size(self.position, size),
+            (None, None) => Ring::new(self.position),
+        };
+        ring.set_outline_thickness(self.outline_thickness);
+        ring.set_outline_color(to_color(self.color));
+        ring.set_restitution(self.restitution);
+        ring.set_gaps(
+            self.gaps
+                .iter()
+                .map(|gap| Gap {
+                    start: gap.start.to_radians(),
+                    length: gap.length.to_radians(),
+                })
+                .collect(),
+        );
+        if let Some(motion) = &self.motion {
+            ring.set_motion(motion.build());
+        }
+        ring.set_health(self.health);
+        ring.set_break_on_escape(self.break_on_escape);
+        ring
+    }
+}
+
+impl ConcentricDesc {
+    fn validate(&self) -> Result<(), String> {
+        if self.count == 0 {
+            return Err(String::from("count must be greater than 0"));
+        }
+        if self.inner_radius <= 0.0 || !self.inner_radius.is_finite() {
+            return Err(String::from("inner_radius must be greater than 0"));
+        }
+        if self.outline_thickness <= 0.0 || !self.outline_thickness.is_finite() {
+            return Err(String::from("outline_thickness must be greater than 0"));
+        }
+        if self.spacing <= self.outline_thickness || !self.spacing.is_finite() {
+            return Err(String::from(
+                "spacing must be greater than outline_thickness, or the rings overlap",
+            ));
+        }
+        if !(0.0..360.0).contains(&self.gap) {
+            return Err(String::from(
+                "gap must be at least 0 and less than 360 degrees",
+            ));
+        }
+        if self.health == Some(0) {
+            return Err(String::from("health must be greater than 0"));
+        }
+        Ok(())
+    }
+
+    /// One ring description per ring, from the inside out.
+    pub fn rings(&self) -> Vec<RingDesc> {
+        (0..self.count)
+            .map(|i| {
+                let gaps = if self.gap > 0.0 {
+                    vec![GapDesc {
+                        start: self.gap_start + self.gap_step * i as f32,
+                        length: self.gap,
+                    }]
+                } else {
+                    Vec::new()
+                };
+                let direction = if self.alternate && i % 2 == 1 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                let motion = (self.spin != 0.0).then_some(MotionDesc::Spin {
+                    speed: self.spin * direction,
+                });
+                let color = match self.colors.len() {
+                    0 => default_color(),
+                    len => self.colors[i % len],
+                };
+                RingDesc {
+                    position: self.position,
+                    radius: Some(self.inner_radius + self.spacing * i as f32),
+                    size: None,
+                    outline_thickness: self.outline_thickness,
+                    color,
+                    restitution: self.restitution,
+                    body: None,
+                    rules: self.rules.clone(),
+                    sound: self.sound.clone(),
+                    gaps,
+                    motion,
+                    health: self.health,
+                    break_on_escape: self.break_on_escape,
+                }
+            })
+            .collect()
+    }
+}
+
+impl MotionDesc {
+    pub fn build(&self) -> Motion {
+        match self {
+            MotionDesc::Spin { speed } => Motion::Spin {
+                speed: speed.to_radians(),
+            },
+            MotionDesc::Oscillate {
+                amplitude,
+                offset,
+                period,
+            } => Motion::Oscillate {
+                amplitude: amplitude.to_radians(),
+                offset: (*offset).into(),
+                period: *period,
+            },
+            MotionDesc::Path { keyframes, repeat } => Motion::Path {
+                keyframes: keyframes
+                    .iter()
+                    .map(|keyframe| Keyframe {
+                        time: keyframe.time,
+                        pose: Pose {
+                            offset: keyframe.offset.into(),
+                            angle: keyframe.angle.to_radians(),
+                        },
+                    })
+                    .collect(),
+                repeat: *repeat,
+            },
+        }
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        let ball = |position| BallDesc {
+            position,
+            radius: None,
+            size: Some(BallSize::Small),
+            outline_thickness: default_thickness(),
+            color: default_color(),
+            velocity: [0.0, 0.0],
+            restitution: default_restitution(),
+            body: default_ball_body(),
+            rules: None,
+            sound: None,
+        };
+        Self {
+            seed: None,
+            window: WindowDesc::default(),
+            gravity: default_gravity(),
+            physics_rate: default_physics_rate(),
+            music: None,
+            melody: None,
+            voices: Voices::default(),
+            polyphony: Polyphony::default(),
+            sounds: SoundsDesc::default(),
+            max_balls: None,
+            balls: vec![ball([290.0, 180.0]), ball([350.0, 180.0])],
+            rings: vec![RingDesc {
+                position: [320.0, 180.0],
+                radius: None,
+                size: Some(RingSize::Large),
+                outline_thickness: default_thickness(),
+                color: default_color(),
+                restitution: default_restitution(),
+                body: None,
+                rules: None,
+                sound: None,
+                gaps: Vec::new(),
+                motion: None,
+                health: None,
+                break_on_escape: false,
+            }],
+            concentric: None,
+            pairs: Vec::new(),
+            end_conditions: Vec::new(),
+        }
+    }
+}
+
+impl Default for SoundsDesc {
+    fn default() -> Self {
+        Self {
+            bank: default_sound_bank(),
+            pairs: Vec::new(),
+        }
+    }
+}
+
+impl Default for WindowDesc {
+    fn default() -> Self {
+        Self {
+            width: 640,
+            height: 360,
+        }
+    }
+}
+
+fn to_color([r, g, b]: [u8; 3]) -> Color {
+    Color::rgb(r, g, b)
+}
+
+fn default_gravity() -> [f32; 2] {
+    [0.0, DEFAULT_GRAVITY]
+}
+
+fn default_physics_rate() -> f32 {
+    60.0
+}
+
+fn default_thickness() -> f32 {
+    5.0
+}
+
+fn default_color() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+fn default_restitution() -> f32 {
+    1.035
+}
+
+fn default_ball_body() -> BodyType {
+    BodyType::Dynamic
+}
+
+fn default_apply_to() -> ApplyTo {
+    ApplyTo::Both
+}
+
+fn default_grow_factor() -> f32 {
+    1.01
+}
+
+fn default_min_radius() -> f32 {
+    1.0
+}
+
+fn default_sample_key() -> u8 {
+    60
+}
+
+fn default_melody_end() -> MelodyEnd {
+    MelodyEnd::Loop
+}
+
+fn default_sound_bank() -> PathBuf {
+    PathBuf::from("sounds")
+}
@@ -0,0 +1,1152 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::Path,
+};
+
+use rand::{rngs::StdRng, SeedableRng};
+use rapier2d::{
+    dynamics::{RigidBodyHandle, RigidBodyType},
+    geometry::{ColliderHandle, CollisionEvent},
+    math::{Point, Real, Vector},
+};
+
+use crate::{
+    ball::Ball,
+    canvas::Canvas,
+    ending::{EndCondition, Ending, Outcome},
+    entity::{Entity, EntityRegistry},
+    impact::Impact,
+    melody::Melody,
+    music::Tuning,
+    physics::{Physics, PhysicsObject},
+    polyphony::Polyphony,
+    render::{Color, Drawable, Renderer},
+    ring::Ring,
+    rules::{self, RuleContext, RuleSet, Selector, Spawn},
+    scene::Scene,
+    sounds::{SoundBank, SoundTrigger, SoundType},
+    synth::Voices,
+};
+
+/// The world being simulated: physics, the objects in it and what happened
+/// to them so far. Knows nothing about windows or audio devices; sounds are
+/// only collected as [`SoundTrigger`]s for a front end to play or mix.
+pub struct Simulation {
+    size: (u32, u32),
+    physics: Physics,
+    balls: Vec<Ball>,
+    rings: Vec<Ring>,
+    entities: EntityRegistry,
+    rules: RuleSet,
+    tuning: Option<Tuning>,
+    melody: Option<Melody>,
+    voices: Voices,
+    sound_bank: SoundBank,
+    /// Bank sounds assigned to single objects.
+    object_sounds: HashMap<Entity, SoundType>,
+    /// Bank sounds for collisions between two kinds of objects, latest
+    /// taking precedence.
+    pair_sounds: Vec<([Selector; 2], SoundType)>,
+    /// The scene's polyphony, with the bank's names set.
+    polyphony: Polyphony,
+    /// Simulated time each ball last made a sound at.
+    last_sounded: HashMap<Entity, f32>,
+    contacts: HashMap<(ColliderHandle, ColliderHandle), Contact>,
+    spawns: Vec<Spawn>,
+    /// Balls to merge at the end of the step, the one to keep first.
+    merges: Vec<[usize; 2]>,
+    max_balls: Option<usize>,
+    /// The ball being dragged around, if any.
+    grab: Option<Grab>,
+    triggers: Vec<SoundTrigger>,
+    collisions: Vec<Collision>,
+    /// Balls entirely inside rings, as `(ball, ring)` indices.
+    inside: HashSet<(usize, usize)>,
+    escapes: Vec<Escape>,
+    /// Rings to break at the end of the step.
+    breaking: Vec<usize>,
+    breaks: Vec<Break>,
+    merged: Vec<Merge>,
+    end_conditions: Vec<EndCondition>,
+    ending: Option<Ending>,
+    rng: StdRng,
+    seed: u64,
+    ticks: u64,
+    collision_count: u64,
+    escape_count: u64,
+    break_count: u64,
+    merge_count: u64,
+}
+
+impl Simulation {
+    pub fn new(scene: &Scene, seed: u64) -> Self {
+        let mut physics = Physics::new();
+        physics.set_gravity(scene.gravity);
+        physics.set_timestep(1.0 / scene.physics_rate);
+
+        let mut entities = EntityRegistry::new();
+        let mut sound_bank = SoundBank::new(&scene.sounds.bank);
+        let mut object_sounds = HashMap::new();
+
+        let balls = scene
+            .balls
+            .iter()
+            .enumerate()
+            .map(|(i, desc)| {
+                let mut ball = desc.build();
+                ball.insert_into_physics(desc.body.into(), &mut physics);
+                entities.insert(ball.rb_handle().unwrap(), Entity::Ball(i));
+                if let Some(name) = &desc.sound {
+                    object_sounds.insert(Entity::Ball(i), sound_bank.add(name));
+                }
+                ball
+            })
+            .collect();
+
+        let rings = scene
+            .rings
+            .iter()
+            .enumerate()
+            .map(|(i, desc)| {
+                let mut ring = desc.build();
+                ring.insert_into_physics(desc.body_type(), &mut physics);
+                entities.insert(ring.rb_handle().unwrap(), Entity::Ring(i));
+                if let Some(name) = &desc.sound {
+                    object_sounds.insert(Entity::Ring(i), sound_bank.add(name));
+                }
+                ring
+            })
+            .collect();
+
+        let pair_sounds = scene
+            .sounds
+            .pairs
+            .iter()
+            .map(|pair| (pair.between, sound_bank.add(&pair.sound)))
+            .collect();
+        let mut polyphony = scene.polyphony.clone();
+        polyphony.set_bank(&sound_bank);
+
+        let mut sim = Self {
+            size: (scene.window.width, scene.window.height),
+            physics,
+            balls,
+            rings,
+            entities,
+            rules: scene.rule_set(),
+            tuning: scene.music.clone(),
+            melody: None,
+            voices: scene.voices,
+            sound_bank,
+            object_sounds,
+            pair_sounds,
+            polyphony,
+            last_sounded: HashMap::new(),
+            contacts: HashMap::new(),
+            spawns: Vec::new(),
+            merges: Vec::new(),
+            max_balls: scene.max_balls,
+            grab: None,
+            triggers: Vec::new(),
+            collisions: Vec::new(),
+            inside: HashSet::new(),
+            escapes: Vec::new(),
+            breaking: Vec::new(),
+            breaks: Vec::new(),
+            merged: Vec::new(),
+            end_conditions: scene.end_conditions.clone(),
+            ending: None,
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+            ticks: 0,
+            collision_count: 0,
+            escape_count: 0,
+            break_count: 0,
+            merge_count: 0,
+        };
+        sim.track_escapes();
+        sim
+    }
+
+    /// Simulated seconds advanced by one [`Simulation::step`].
+    pub fn timestep(&self) -> f32 {
+        self.physics.timestep()
+    }
+
+    /// Simulated seconds since the start of the run.
+    pub fn time(&self) -> f32 {
+        self.ticks as f32 * self.timestep()
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Every ball the run has had, indexed like [`Entity::Ball`]. Balls that
+    /// merged into another or were removed stay, marked
+    /// [`Ball::is_absorbed`], so the others keep their index; count those
+    /// left with [`Simulation::live_balls`].
+    pub fn balls(&self) -> &[Ball] {
+        &self.balls
+    }
+
+    /// Balls still in the world, leaving out those merged or removed.
+    pub fn live_balls(&self) -> usize {
+        self.balls.iter().filter(|ball| !ball.is_absorbed()).count()
+    }
+
+    pub fn rings(&self) -> &[Ring] {
+        &self.rings
+    }
+
+    /// Plays `melody` one note per collision from now on, or goes back to
+    /// pitching bounces by speed with `None`.
+    pub fn set_melody(&mut self, melody: Option<Melody>) {
+        self.melody = melody;
+    }
+
+    /// Pitches bounces can sound at, for rendering voices ahead of time: the
+    /// notes of the melody, or else of the scene's scale. Empty when bounces
+    /// are pitched continuously.
+    pub fn voice_pitches(&self) -> Vec<f32> {
+        match (&self.melody, &self.tuning) {
+            (Some(melody), _) => melody.pitches(),
+            (None, Some(tuning)) => tuning.pitches(),
+            (None, None) => Vec::new(),
+        }
+    }
+
+    /// The synthesized voices the sound triggers may refer to.
+    pub fn voices(&self) -> &Voices {
+        &self.voices
+    }
+
+    /// The sound types outside the bank the run can trigger without a voice
+    /// to synthesize them: the bounce sample unless every collision has a
+    /// voice, and the shatter sample if any ring can break.
+    pub fn sampled_sounds(&self) -> Vec<SoundType> {
+        let mut sounds = Vec::new();
+        if self.voices.ball.is_none() {
+            sounds.push(SoundType::Bounce);
+        }
+        if self
+            .rings
+            .iter()
+            .any(|ring| ring.health().is_some() || ring.breaks_on_escape())
+        {
+            sounds.push(SoundType::Shatter);
+        }
+        sounds
+    }
+
+    /// The samples the sound triggers may refer to by [`SoundType::Bank`].
+    pub fn sound_bank(&self) -> &SoundBank {
+        &self.sound_bank
+    }
+
+    /// How many of the sound triggers may play at once, with the names of
+    /// the bank sounds set.
+    pub fn polyphony(&self) -> &Polyphony {
+        &self.polyphony
+    }
+
+    /// The collision rules in effect, for attaching custom ones.
+    pub fn rules_mut(&mut self) -> &mut RuleSet {
+        &mut self.rules
+    }
+
+    /// Steps the simulation until `limit` is reached or the run ends, and
+    /// reports the state it stopped in. The sounds that would have played are
+    /// kept for [`Simulation::take_sound_triggers`].
+    pub fn run_headless(&mut self, limit: RunLimit) -> RunReport {
+        let ticks = limit.ticks(self.timestep());
+        for _ in 0..ticks {
+            if self.ending.is_some() {
+                break;
+            }
+            self.step();
+        }
+        self.report()
+    }
+
+    /// Like [`Simulation::run_headless`], but also renders `fps` frames per
+    /// simulated second on the CPU and saves them as numbered PNG files in
+    /// `dir`.
+    pub fn run_recording(
+        &mut self,
+        limit: RunLimit,
+        fps: f32,
+        dir: &Path,
+    ) -> Result<RunReport, png::EncodingError> {
+        std::fs::create_dir_all(dir)?;
+        let timestep = self.timestep();
+        let frames = (limit.seconds(timestep) * fps).ceil() as u64;
+        let mut canvas = Canvas::new(self.size.0, self.size.1);
+        let mut accumulator = 0.0;
+        for frame in 0..frames {
+            self.draw(&mut canvas, accumulator / timestep);
+            canvas.save_png(dir.join(format!("frame_{frame:06}.png")))?;
+            if self.ending.is_some() {
+                break;
+            }
+
+            accumulator += 1.0 / fps;
+            while accumulator >= timestep && self.ending.is_none() {
+                self.step();
+                accumulator -= timestep;
+            }
+        }
+        Ok(self.report())
+    }
+
+    /// Drains the sounds triggered since the last call, oldest first.
+    pub fn take_sound_triggers(&mut self) -> Vec<SoundTrigger> {
+        std::mem::take(&mut self.triggers)
+    }
+
+    /// Drains the collisions reacted to since the last call, oldest first.
+    pub fn take_collisions(&mut self) -> Vec<Collision> {
+        std::mem::take(&mut self.collisions)
+    }
+
+    /// Drains the balls that left a ring since the last call, oldest first.
+    pub fn take_escapes(&mut self) -> Vec<Escape> {
+        std::mem::take(&mut self.escapes)
+    }
+
+    /// Drains the rings that broke since the last call, oldest first.
+    pub fn take_breaks(&mut self) -> Vec<Break> {
+        std::mem::take(&mut self.breaks)
+    }
+
+    /// Drains the balls that merged since the last call, oldest first.
+    pub fn take_merges(&mut self) -> Vec<Merge> {
+        std::mem::take(&mut self.merged)
+    }
+
+    /// The ball under `point`, as balls were placed at the end of the last
+    /// step.
+    pub fn ball_at(&self, point: Vector<Real>) -> Option<usize> {
+        self.physics
+            .bodies_at(point.into())
+            .into_iter()
+            .find_map(|handle| match self.entities.get(handle)? {
+                Entity::Ball(i) => Some(i),
+                Entity::Ring(_) => None,
+            })
+    }
+
+    /// The run's random number generator, for random choices made outside
+    /// of a step that should still replay with the seed.
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    /// Adds `ball` as a dynamic ball with the default ball rules, unless the
+    /// scene's cap on balls is reached. Returns its index.
+    pub fn add_ball(&mut self, mut ball: Ball) -> Option<usize> {
+        if self.max_balls.is_some_and(|max| self.live_balls() >= max) {
+            return None;
+        }
+        let index = self.balls.len();
+        ball.insert_into_physics(RigidBodyType::Dynamic, &mut self.physics);
+        self.entities
+            .insert(ball.rb_handle().unwrap(), Entity::Ball(index));
+        self.rules
+            .set(Entity::Ball(index), rules::default_ball_rules());
+        self.balls.push(ball);
+        Some(index)
+    }
+
+    /// Takes ball `index` out of the world. Returns whether it was still
+    /// there.
+    pub fn remove_ball(&mut self, index: usize) -> bool {
+        let Some(handle) = self.balls[index].remove(&mut self.physics) else {
+            return false;
+        };
+        self.entities.remove(handle);
+        self.inside.retain(|&(ball, _)| ball != index);
+        if self.grab.is_some_and(|grab| grab.ball == index) {
+            self.grab = None;
+        }
+        true
+    }
+
+    /// Starts dragging ball `index` by the spot under `point`. Until it is
+    /// released it is pushed along to follow [`Simulation::drag_to`] every
+    /// step, still colliding with whatever is in the way.
+    pub fn grab_ball(&mut self, index: usize, point: Vector<Real>) {
+        let ball = &self.balls[index];
+        if ball.is_absorbed() {
+            return;
+        }
+        self.grab = Some(Grab {
+            ball: index,
+            offset: ball.position() - point,
+            target: point,
+            previous_target: point,
+            velocity: Vector::zeros(),
+        });
+    }
+
+    /// Moves the point the grabbed ball is held by.
+    pub fn drag_to(&mut self, point: Vector<Real>) {
+        if let Some(grab) = &mut self.grab {
+            grab.target = point;
+        }
+    }
+
+    /// Lets go of the grabbed ball, flinging it on at the speed it was being
+    /// dragged at.
+    pub fn release_ball(&mut self) {
+        let Some(grab) = self.grab.take() else {
+            return;
+        };
+        let Some(handle) = self.balls[grab.ball].rb_handle() else {
+            return;
+        };
+        if let Some(rb) = self.physics.rigidbody_set.get_mut(handle) {
+            rb.set_linvel(grab.velocity, true);
+        }
+    }
+
+    /// How the run ended, once one of the scene's end conditions was met.
+    /// Stepping on after that is allowed but changes nothing about it.
+    pub fn ending(&self) -> Option<Ending> {
+        self.ending
+    }
+
+    pub fn report(&self) -> RunReport {
+        RunReport {
+            seed: self.seed,
+            ticks: self.ticks,
+            simulated_seconds: self.time(),
+            collisions: self.collision_count,
+            escapes: self.escape_count,
+            broken_rings: self.break_count,
+            merges: self.merge_count,
+            ending: self.ending,
+            balls: self
+                .balls
+                .iter()
+                .enumerate()
+                .filter(|(_, ball)| !ball.is_absorbed())
+                .map(|(index, ball)| BallReport {
+                    index,
+                    position: (ball.position().x, ball.position().y),
+                    radius: ball.radius(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Advances the world by one physics step and reacts to the collisions
+    /// that ended during it. Balls spawned by rules join at the end of the
+    /// step.
+    pub fn step(&mut self) {
+        let velocities: HashMap<_, _> = self
+            .balls
+            .iter()
+            .filter_map(|ball| {
+                let handle = ball.rb_handle()?;
+                Some((handle, *self.physics.rigidbody_set.get(handle)?.linvel()))
+            })
+            .collect();
+        self.drag_grabbed_ball();
+        let next_time = self.time() + self.timestep();
+        for ring in &self.rings {
+            ring.drive(&mut self.physics, next_time);
+        }
+        self.physics.step();
+        for ball in &mut self.balls {
+            ball.update(&mut self.physics);
+        }
+        for ring in &mut self.rings {
+            ring.update(&mut self.physics);
+        }
+        let events = self.physics.get_collision_events();
+        for event in &events {
+            if let CollisionEvent::Started(collider1, collider2, _) = *event {
+                self.begin_contact(collider1, collider2, &velocities);
+            }
+        }
+        self.track_contact_forces();
+        for event in events {
+            if event.stopped() {
+                let contact = self.take_contact(event.collider1(), event.collider2());
+                if !event.removed() {
+                    self.react_to_collision(event, contact);
+                }
+            }
+        }
+        self.merge_balls();
+        self.insert_spawns();
+        self.physics.cleanup();
+        self.ticks += 1;
+        self.track_escapes();
+        for ring in std::mem::take(&mut self.breaking) {
+            self.shatter(ring);
+        }
+        if self.ending.is_none() {
+            self.ending = self.check_end_conditions();
+        }
+    }
+
+    /// Clears `renderer` and draws every object `alpha` of the way between
+    /// the previous and the current physics step. Once the run has ended, the
+    /// final screen is tinted by its outcome.
+    pub fn draw(&self, renderer: &mut dyn Renderer, alpha: f32) {
+        renderer.clear(Color::BLACK);
+        for ball in &self.balls {
+            ball.draw(renderer, alpha);
+        }
+        for ring in &self.rings {
+            ring.draw(renderer, alpha);
+        }
+        if let Some(ending) = self.ending {
+            renderer.tint(match ending.outcome() {
+                Outcome::Win => WIN_TINT,
+                Outcome::Lose => LOSE_TINT,
+            });
+        }
+    }
+
+    /// The first end condition, in scene order, that the world is now in.
+    fn check_end_conditions(&self) -> Option<Ending> {
+        let condition = self
+            .end_conditions
+            .iter()
+            .find(|condition| self.is_met(condition))?;
+        Some(Ending {
+            time: self.time(),
+            condition: *condition,
+        })
+    }
+
+    fn is_met(&self, condition: &EndCondition) -> bool {
+        match *condition {
+            EndCondition::Fill { fraction, .. } => self.inside.iter().any(|&(b, r)| {
+                let ball = &self.balls[b];
+                ball.radius() + ball.outline_thickness() >= fraction * self.rings[r].radius()
+            }),
+            EndCondition::Escaped { .. } => self.escape_count > 0 && self.inside.is_empty(),
+            EndCondition::Collisions { count, .. } => self.collision_count >= count,
+            EndCondition::Time { seconds, .. } => {
+                self.ticks >= RunLimit::Seconds(seconds).ticks(self.timestep())
+            }
+            EndCondition::Balls { count, .. } => self.live_balls() >= count,
+        }
+    }
+
+    /// Sets the grabbed ball moving so that it reaches the point it is held
+    /// by at the end of the step, and keeps track of how fast that point
+    /// moves.
+    fn drag_grabbed_ball(&mut self) {
+        let timestep = self.timestep();
+        let Some(grab) = &mut self.grab else {
+            return;
+        };
+        let pointer_velocity = (grab.target - grab.previous_target) / timestep;
+        grab.velocity = grab.velocity.lerp(&pointer_velocity, FLING_SMOOTHING);
+        grab.previous_target = grab.target;
+        let Some(rb) = self.balls[grab.ball]
+            .rb_handle()
+            .and_then(|handle| self.physics.rigidbody_set.get_mut(handle))
+        else {
+            return;
+        };
+        let velocity = (grab.target + grab.offset - rb.translation()) / timestep;
+        rb.set_linvel(velocity, true);
+    }
+
+    /// Notes which balls are now entirely inside which rings, and which ones
+    /// were and are now entirely outside, through a gap or otherwise. Balls
+    /// that start out or are spawned outside a ring can't escape it until
+    /// they have been inside.
+    fn track_escapes(&mut self) {
+        let time = self.time();
+        for (b, ball) in self.balls.iter().enumerate() {
+            if ball.is_absorbed() {
+                continue;
+            }
+            let reach = ball.radius() + ball.outline_thickness();
+            for (r, ring) in self.rings.iter().enumerate() {
+                if ring.is_broken() {
+                    continue;
+                }
+                let distance = (ball.position() - ring.position()).norm();
+                if distance + reach <= ring.radius() {
+                    self.inside.insert((b, r));
+                } else if distance - reach > ring.radius() + ring.outline_thickness()
+                    && self.inside.remove(&(b, r))
+                {
+                    self.escapes.push(Escape {
+                        time,
+                        ball: b,
+                        ring: r,
+                    });
+                    self.escape_count += 1;
+                    if ring.breaks_on_escape() {
+                        self.breaking.push(r);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Breaks ring `index` apart with a sound pitched lower the bigger it
+    /// is, unless it is broken already.
+    fn shatter(&mut self, index: usize) {
+        let time = self.time();
+        let ring = &mut self.rings[index];
+        if ring.is_broken() {
+            return;
+        }
+        if let Some(handle) = ring.shatter(&mut self.physics) {
+            self.entities.remove(handle);
+        }
+        let pan = (ring.position().x / self.size.0 as f32 * 2.0 - 1.0).clamp(-1.0, 1.0);
+        self.triggers.push(SoundTrigger {
+            time,
+            sound: SoundType::Shatter,
+            pitch: (SHATTER_PITCH_RADIUS / ring.radius())
+                .sqrt()
+                .clamp(0.5, 2.0),
+            volume: BOUNCE_VOLUME,
+            pan,
+            duration: None,
+        });
+        self.inside.retain(|&(_, r)| r != index);
+        self.breaks.push(Break { time, ring: index });
+        self.break_count += 1;
+    }
+
+    /// Starts keeping track of two colliders that began touching, with the
+    /// velocities their bodies had before the step.
+    fn begin_contact(
+        &mut self,
+        collider1: ColliderHandle,
+        collider2: ColliderHandle,
+        velocities: &HashMap<RigidBodyHandle, Vector<Real>>,
+    ) {
+        let velocity = |collider| {
+            self.physics
+                .parent_of(collider)
+                .and_then(|handle| velocities.get(&handle).copied())
+                .unwrap_or_else(Vector::zeros)
+        };
+        let contact = Contact {
+            geometry: self.physics.contact(collider1, collider2),
+            velocities: [velocity(collider1), velocity(collider2)],
+            impulse: 0.0,
+        };
+        self.contacts.insert((collider1, collider2), contact);
+    }
+
+    /// Adds up the impulses reported by contact force events. They only see
+    /// what the solver's last substep did, so they miss most of a quick
+    /// bounce but catch objects being pushed together for a while.
+    fn track_contact_forces(&mut self) {
+        let timestep = self.timestep();
+        for event in self.physics.get_contact_force_events() {
+            let (collider1, collider2) = (event.collider1, event.collider2);
+            let (key, geometry) = if self.contacts.contains_key(&(collider1, collider2)) {
+                let geometry = self.physics.contact(collider1, collider2);
+                ((collider1, collider2), geometry)
+            } else {
+                let geometry = self.physics.contact(collider2, collider1);
+                ((collider2, collider1), geometry)
+            };
+            if let Some(contact) = self.contacts.get_mut(&key) {
+                contact.impulse += event.total_force_magnitude * timestep;
+                contact.geometry = geometry.or(contact.geometry);
+            }
+        }
+    }
+
+    fn take_contact(
+        &mut self,
+        collider1: ColliderHandle,
+        collider2: ColliderHandle,
+    ) -> Option<Contact> {
+        self.contacts
+            .remove(&(collider1, collider2))
+            .or_else(|| self.contacts.remove(&(collider2, collider1)))
+    }
+
+    /// How two colliders hit each other over `contact`. Without one, the
+    /// point and normal are guessed from where the objects are and the speed
+    /// from how fast they move apart.
+    fn impact(
+        &self,
+        [collider1, collider2]: [ColliderHandle; 2],
+        [first, second]: [Entity; 2],
+        contact: Option<&Contact>,
+    ) -> Impact {
+        let (point, normal) = match contact.and_then(|contact| contact.geometry) {
+            Some((point, normal)) => (point.coords, normal),
+            None => self.fallback_geometry(first, second),
+        };
+        let [before1, before2] = match contact {
+            Some(contact) => contact.velocities,
+            None => [self.velocity(collider1), self.velocity(collider2)],
+        };
+        let momentum = |collider, before: Vector<Real>| {
+            self.physics
+                .parent_of(collider)
+                .and_then(|handle| self.physics.rigidbody_set.get(handle))
+                .filter(|rb| rb.is_dynamic())
+                .map_or(0.0, |rb| rb.mass() * (rb.linvel() - before).norm())
+        };
+        let strength = momentum(collider1, before1)
+            .max(momentum(collider2, before2))
+            .max(contact.map_or(0.0, |contact| contact.impulse));
+        Impact {
+            point,
+            normal,
+            strength,
+            speed: (before1 - before2).dot(&normal).abs(),
+        }
+    }
+
+    fn react_to_collision(&mut self, event: CollisionEvent, contact: Option<Contact>) {
+        let (collider1, collider2) = (event.collider1(), event.collider2());
+        if self.physics.is_collider_removed(collider1)
+            || self.physics.is_collider_removed(collider2)
+        {
+            return;
+        }
+        let (Some(first), Some(second)) = (
+            self.entities.get_by_collider(&self.physics, collider1),
+            self.entities.get_by_collider(&self.physics, collider2),
+        ) else {
+            return;
+        };
+        let impact = self.impact([collider1, collider2], [first, second], contact.as_ref());
+        let time = self.time() + self.timestep();
+        let sound = self.sound_for(first, second);
+        let pan = (impact.point.x / self.size.0 as f32 * 2.0 - 1.0).clamp(-1.0, 1.0);
+        // Without a contact nothing was measured, so play it at average volume.
+        let loudness = contact.map_or(1.0, |_| loudness(impact.relative_strength()));
+        self.collision_count += 1;
+        self.collisions.push(Collision {
+            time,
+            first,
+            second,
+            impact,
+        });
+        let audible = self.may_sound(time, [first, second]);
+        let trigger = match &mut self.melody {
+            _ if !audible => None,
+            Some(melody) => melody.next_note(impact.speed).map(|note| SoundTrigger {
+                time,
+                sound,
+                pitch: melody.pitch(note),
+                volume: BOUNCE_VOLUME * loudness * note.velocity as f32 / 64.0,
+                pan,
+                duration: Some(note.duration),
+            }),
+            None => Some(SoundTrigger {
+                time,
+                sound,
+                pitch: self.pitch(impact.speed),
+                volume: BOUNCE_VOLUME * loudness,
+                pan,
+                duration: None,
+            }),
+        };
+        self.triggers.extend(trigger);
+        if let (Entity::Ball(_), Entity::Ring(ring)) | (Entity::Ring(ring), Entity::Ball(_)) =
+            (first, second)
+        {
+            if self.rings[ring].hit() {
+                self.breaking.push(ring);
+            }
+        }
+        self.react(first, second, impact);
+        self.react(second, first, impact.flipped());
+    }
+
+    /// Whether a collision between `entities` at `time` makes a sound: not
+    /// if every ball in it sounded less than the retrigger interval ago.
+    /// Marks the balls as sounding now if it does.
+    fn may_sound(&mut self, time: f32, entities: [Entity; 2]) -> bool {
+        let balls = entities
+            .into_iter()
+            .filter(|entity| matches!(entity, Entity::Ball(_)));
+        let recent = |ball| {
+            self.last_sounded
+                .get(&ball)
+                .is_some_and(|&last| time - last < self.polyphony.retrigger)
+        };
+        if balls.clone().count() > 0 && balls.clone().all(recent) {
+            return false;
+        }
+        for ball in balls {
+            self.last_sounded.insert(ball, time);
+        }
+        true
+    }
+
+    /// Where two objects no contact was seen for most likely touched, and the
+    /// normal there from `first` towards `second`: at the ball's edge facing
+    /// the ring it hit, or halfway between two balls' centers.
+    fn fallback_geometry(&self, first: Entity, second: Entity) -> (Vector<Real>, Vector<Real>) {
+        let position = |entity| match entity {
+            Entity::Ball(i) => self.balls[i].position(),
+            Entity::Ring(i) => self.rings[i].position(),
+        };
+        let towards = |from: Vector<Real>, to: Vector<Real>| {
+            (to - from)
+                .try_normalize(Real::EPSILON)
+                .unwrap_or_else(Vector::y)
+        };
+        match (first, second) {
+            (Entity::Ball(ball), Entity::Ring(ring)) | (Entity::Ring(ring), Entity::Ball(ball)) => {
+                let (ball, ring) = (&self.balls[ball], &self.rings[ring]);
+                let offset = ball.position() - ring.position();
+                // A ball inside the ring hits its wall on the outward side.
+                let outward = towards(ring.position(), ball.position());
+                let ball_normal = if offset.norm() < ring.radius() {
+                    outward
+                } else {
+                    -outward
+                };
+                let point = ball.position() + ball_normal * ball.radius();
+                match first {
+                    Entity::Ball(_) => (point, ball_normal),
+                    Entity::Ring(_) => (point, -ball_normal),
+                }
+            }
+            _ => (
+                (position(first) + position(second)) / 2.0,
+                towards(position(first), position(second)),
+            ),
+        }
+    }
+
+    /// The sound assigned to the pair, else a ball's, else the ring's. Without
+    /// any, a ring's voice if a ring was hit, else a ball's, else the plain
+    /// bounce sample.
+    fn sound_for(&self, first: Entity, second: Entity) -> SoundType {
+        let pair_sound = self.pair_sounds.iter().rev().find(|([a, b], _)| {
+            (a.matches(first) && b.matches(second)) || (a.matches(second) && b.matches(first))
+        });
+        if let Some(&(_, sound)) = pair_sound {
+            return sound;
+        }
+        let (ball_first, has_ring) = match (first, second) {
+            (Entity::Ring(_), _) => ([second, first], true),
+            (_, Entity::Ring(_)) => ([first, second], true),
+            _ => ([first, second], false),
+        };
+        let own_sound = ball_first
+            .iter()
+            .find_map(|entity| self.object_sounds.get(entity));
+        if let Some(&sound) = own_sound {
+            return sound;
+        }
+        if has_ring && self.voices.ring.is_some() {
+            SoundType::Ring
+        } else if self.voices.ball.is_some() {
+            SoundType::Ball
+        } else {
+            SoundType::Bounce
+        }
+    }
+
+    /// Playback rate for a bounce at `speed`: continuous, or the nearest
+    /// note of the scene's scale when it has one.
+    fn pitch(&self, speed: f32) -> f32 {
+        match &self.tuning {
+            Some(tuning) => tuning.pitch(pitch(speed) / MAX_PITCH),
+            None => pitch(speed),
+        }
+    }
+
+    fn velocity(&self, collider: ColliderHandle) -> Vector<Real> {
+        self.physics
+            .parent_of(collider)
+            .and_then(|handle| self.physics.rigidbody_set.get(handle))
+            .map_or_else(Vector::zeros, |rb| *rb.linvel())
+    }
+
+    fn react(&mut self, target: Entity, other: Entity, impact: Impact) {
+        let mut ctx = RuleContext {
+            target,
+            other,
+            impact,
+            physics: &mut self.physics,
+            balls: &mut self.balls,
+            rings: &mut self.rings,
+            rng: &mut self.rng,
+            spawns: &mut self.spawns,
+            merges: &mut self.merges,
+            max_balls: self.max_balls,
+        };
+        for rule in self.rules.rules_for(target, other).iter() {
+            rule.apply(&mut ctx);
+        }
+    }
+
+    /// Merges the balls rules asked to during the step, in the order they
+    /// asked, into one as big as both at their center of mass, keeping
+    /// their momentum. Balls already merged away in the step are skipped.
+    fn merge_balls(&mut self) {
+        let time = self.time() + self.timestep();
+        for [keep, absorbed] in std::mem::take(&mut self.merges) {
+            let (Some(keep_handle), Some(absorbed_handle)) = (
+                self.balls[keep].rb_handle(),
+                self.balls[absorbed].rb_handle(),
+            ) else {
+                continue;
+            };
+            let body = |handle| {
+                let rb = self.physics.rigidbody_set.get(handle)?;
+                Some((rb.mass(), *rb.translation(), *rb.linvel()))
+            };
+            let (Some((mass1, position1, velocity1)), Some((mass2, position2, velocity2))) =
+                (body(keep_handle), body(absorbed_handle))
+            else {
+                continue;
+            };
+            let mass = mass1 + mass2;
+            let position = (position1 * mass1 + position2 * mass2) / mass;
+            let velocity = (velocity1 * mass1 + velocity2 * mass2) / mass;
+            let radius = self.balls[keep]
+                .radius()
+                .hypot(self.balls[absorbed].radius());
+
+            let ball = &mut self.balls[keep];
+            ball.set_radius(radius);
+            let collider = ball.create_collider(self.physics.gravity());
+            self.physics.set_collider(keep_handle, collider);
+            if let Some(rb) = self.physics.rigidbody_set.get_mut(keep_handle) {
+                rb.set_translation(position, true);
+                rb.set_linvel(velocity, true);
+            }
+            if let Some(handle) = self.balls[absorbed].absorb_into(position, &mut self.physics) {
+                self.entities.remove(handle);
+            }
+            self.inside.retain(|&(ball, _)| ball != absorbed);
+
+            let pan = (position.x / self.size.0 as f32 * 2.0 - 1.0).clamp(-1.0, 1.0);
+            self.triggers.push(SoundTrigger {
+                time,
+                sound: self.sound_for(Entity::Ball(keep), Entity::Ball(absorbed)),
+                pitch: (MERGE_PITCH_RADIUS / radius).sqrt().clamp(0.25, 2.0),
+                volume: BOUNCE_VOLUME,
+                pan,
+                duration: None,
+            });
+            self.merged.push(Merge {
+                time,
+                ball: keep,
+                absorbed,
+            });
+            self.merge_count += 1;
+        }
+    }
+
+    /// Adds the balls rules spawned during the step, in the order they asked
+    /// for them, leaving out those past the scene's cap.
+    fn insert_spawns(&mut self) {
+        let room = self
+            .max_balls
+            .map_or(usize::MAX, |max| max.saturating_sub(self.live_balls()));
+        for Spawn { mut ball, parent } in self.spawns.drain(..).take(room) {
+            let entity = Entity::Ball(self.balls.len());
+            ball.insert_into_physics(RigidBodyType::Dynamic, &mut self.physics);
+            self.entities.insert(ball.rb_handle().unwrap(), entity);
+            self.rules.set(entity, self.rules.get(Entity::Ball(parent)));
+            if let Some(&sound) = self.object_sounds.get(&Entity::Ball(parent)) {
+                self.object_sounds.insert(entity, sound);
+            }
+            self.balls.push(ball);
+        }
+    }
+}
+
+/// What is known about two colliders while they touch.
+struct Contact {
+    /// Where they touch and the normal there, once the narrow phase
+    /// reported it.
+    geometry: Option<(Point<Real>, Vector<Real>)>,
+    /// Linear velocities of both bodies right before the contact began.
+    velocities: [Vector<Real>; 2],
+    /// Impulse added up from contact force events.
+    impulse: f32,
+}
+
+/// A ball held by a point that moves, such as the mouse pointer.
+#[derive(Clone, Copy)]
+struct Grab {
+    ball: usize,
+    /// From the point to the ball's center.
+    offset: Vector<Real>,
+    target: Vector<Real>,
+    /// Where the point was at the start of the last step.
+    previous_target: Vector<Real>,
+    /// How fast the point has been moving lately, to fling the ball with.
+    velocity: Vector<Real>,
+}
+
+/// Two objects that stopped touching during a step.
+#[derive(Clone, Copy, Debug)]
+pub struct Collision {
+    /// Simulated time at the end of the step the collision ended in.
+    pub time: f32,
+    pub first: Entity,
+    pub second: Entity,
+    /// How hard they hit, with the normal pointing from `first` to `second`.
+    pub impact: Impact,
+}
+
+/// A ball that was inside a ring and is now entirely outside it.
+#[derive(Clone, Copy, Debug)]
+pub struct Escape {
+    /// Simulated time at the end of the step the ball got clear in.
+    pub time: f32,
+    /// Index of the ball in [`Simulation::balls`].
+    pub ball: usize,
+    /// Index of the ring in [`Simulation::rings`].
+    pub ring: usize,
+}
+
+/// A ring that took its last hit or that a ball got out of, and broke.
+#[derive(Clone, Copy, Debug)]
+pub struct Break {
+    /// Simulated time at the end of the step the ring broke in.
+    pub time: f32,
+    /// Index of the ring in [`Simulation::rings`].
+    pub ring: usize,
+}
+
+/// Two balls that merged into one.
+#[derive(Clone, Copy, Debug)]
+pub struct Merge {
+    /// Simulated time at the end of the step they merged in.
+    pub time: f32,
+    /// Index of the ball that took in the other, in [`Simulation::balls`].
+    pub ball: usize,
+    /// Index of the ball that is gone.
+    pub absorbed: usize,
+}
+
+/// How long [`Simulation::run_headless`] keeps stepping.
+#[derive(Clone, Copy, Debug)]
+pub enum RunLimit {
+    Ticks(u64),
+    Seconds(f32),
+}
+
+impl RunLimit {
+    fn ticks(self, timestep: f32) -> u64 {
+        match self {
+            RunLimit::Ticks(ticks) => ticks,
+            RunLimit::Seconds(seconds) => (seconds / timestep).ceil() as u64,
+        }
+    }
+
+    fn seconds(self, timestep: f32) -> f32 {
+        match self {
+            RunLimit::Ticks(ticks) => ticks as f32 * timestep,
+            RunLimit::Seconds(seconds) => seconds,
+        }
+    }
+}
+
+pub struct RunReport {
+    pub seed: u64,
+    pub ticks: u64,
+    pub simulated_seconds: f32,
+    pub collisions: u64,
+    pub escapes: u64,
+    pub broken_rings: u64,
+    pub merges: u64,
+    /// How the run ended, if it did before it was stopped.
+    pub ending: Option<Ending>,
+    /// The balls left, leaving out those merged into others.
+    pub balls: Vec<BallReport>,
+}
+
+pub struct BallReport {
+    /// Index of the ball in [`Simulation::balls`].
+    pub index: usize,
+    pub position: (f32, f32),
+    pub radius: f32,
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed: {}, ticks: {}, simulated: {:.2}s, collisions: {}",
+            self.seed, self.ticks, self.simulated_seconds, self.collisions
+        )?;
+        if self.escapes > 0 {
+            write!(f, ", escapes: {}", self.escapes)?;
+        }
+        if self.broken_rings > 0 {
+            write!(f, ", broken rings: {}", self.broken_rings)?;
+        }
+        if self.merges > 0 {
+            write!(f, ", merges: {}", self.merges)?;
+        }
+        writeln!(f)?;
+        if let Some(ending) = &self.ending {
+            writeln!(f, "ended: {ending}")?;
+        }
+        for ball in &self.balls {
+            writeln!(
+                f,
+                "ball {}: position ({:.2}, {:.2}), radius {:.2}",
+                ball.index, ball.position.0, ball.position.1, ball.radius
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Volume of a bounce of average strength, and of a melody note at MIDI's
+/// default velocity of 64.
+const BOUNCE_VOLUME: f32 = 1.5;
+
+/// How much louder than an average bounce one `relative_strength` times as
+/// strong is, following the roughly logarithmic way loudness is perceived.
+fn loudness(relative_strength: f32) -> f32 {
+    (1.0 + relative_strength.max(1e-3).log10() / 2.0).clamp(0.1, 2.0)
+}
+
+/// Laid over the final screen of a run that was won or lost.
+const WIN_TINT: Color = Color::rgba(40, 200, 90, 70);
+const LOSE_TINT: Color = Color::rgba(220, 40, 40, 70);
+
+/// How much of the pointer's latest speed goes into a dragged ball's fling
+/// velocity each step, the rest being what it was before. Evens out pointer
+/// updates arriving at a different rate than physics steps.
+const FLING_SMOOTHING: f32 = 0.3;
+
+/// Radius of a ring that shatters at the sample's own pitch.
+const SHATTER_PITCH_RADIUS: f32 = 150.0;
+
+/// Radius of a merged ball that sounds at the sample's own pitch.
+const MERGE_PITCH_RADIUS: f32 = 15.0;
+
+const MAX_PITCH: f32 = 4.0;
+
+fn pitch(magnitude: f32) -> f32 {
+    let magnitude = magnitude as u32;
+    match magnitude {
+        0..=200 => magnitude as f32 / 200.0,
+        201..=1000 => 1.0 + (magnitude - 200) as f32 / 800.0,
+        1001..=2000 => 2.0 + (magnitude - 1000) as f32 / 1000.0,
+        2001..=4000 => 3.0 + (magnitude - 2000) as f32 / 2000.0,
+        _ => MAX_PITCH,
+    }
+}
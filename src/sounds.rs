@@ -1,68 +1,334 @@
-use std::collections::HashMap;
+use std::path::PathBuf;
+#[cfg(feature = "sfml")]
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, Instant},
+};
 
+#[cfg(feature = "sfml")]
 use sfml::{
     audio::{Sound, SoundBuffer, SoundSource, SoundStatus},
     SfBox,
 };
 
-use crate::util::assets;
+use crate::assets::{AssetError, AssetPaths};
+#[cfg(feature = "sfml")]
+use crate::{
+    assets::Assets,
+    polyphony::{Allocation, PlayingVoice, Polyphony},
+    synth::{cents_to_pitch, pitch_in_cents, Voices},
+};
 
+#[cfg(feature = "sfml")]
+#[derive(Default)]
 pub struct Sounds<'s> {
-    playing: Vec<Sound<'s>>,
+    playing: Vec<Playing<'s>>,
+    polyphony: Polyphony,
+}
+
+#[cfg(feature = "sfml")]
+struct Playing<'s> {
+    sound: Sound<'s>,
+    stop_at: Option<Instant>,
+    voice: PlayingVoice,
 }
 
+#[cfg(feature = "sfml")]
 impl<'s> Sounds<'s> {
     pub fn new() -> Self {
-        Self {
-            playing: Vec::new(),
-        }
+        Self::default()
+    }
+
+    /// Limits how many sounds play at once from now on.
+    pub fn set_polyphony(&mut self, polyphony: Polyphony) {
+        self.polyphony = polyphony;
     }
 
-    pub fn play(&mut self, sound_buffer: &'s SfBox<SoundBuffer>, pitch: f32) {
+    /// Starts the sound `trigger` asks for from `sound_buffer` at `pitch`,
+    /// stopping another one first or not playing it at all if too many are
+    /// playing already. SFML only pans mono buffers.
+    pub fn play(
+        &mut self,
+        sound_buffer: &'s SfBox<SoundBuffer>,
+        pitch: f32,
+        trigger: &SoundTrigger,
+    ) {
+        self.remove_stopped();
+        let voices: Vec<_> = self.playing.iter().map(|playing| playing.voice).collect();
+        match self
+            .polyphony
+            .allocate(&voices, trigger.sound, trigger.volume)
+        {
+            Allocation::Play => {}
+            Allocation::Steal(index) => {
+                self.playing.remove(index).sound.stop();
+            }
+            Allocation::Drop => return,
+        }
+
+        let pan = trigger.pan;
         let mut sound = Sound::with_buffer(sound_buffer);
-        sound.set_volume(1.5);
+        sound.set_volume(trigger.volume);
         sound.set_pitch(pitch);
+        sound.set_relative_to_listener(true);
+        sound.set_position((pan, 0.0, -(1.0 - pan * pan).max(0.0).sqrt()));
         sound.play();
-        self.playing.push(sound);
+        let stop_at = trigger
+            .duration
+            .map(|seconds| Instant::now() + Duration::from_secs_f32(seconds));
+        self.playing.push(Playing {
+            sound,
+            stop_at,
+            voice: PlayingVoice {
+                sound: trigger.sound,
+                volume: trigger.volume,
+            },
+        });
     }
 
     pub fn update(&mut self) {
+        let now = Instant::now();
+        for playing in &mut self.playing {
+            if playing.stop_at.is_some_and(|stop_at| stop_at <= now) {
+                playing.sound.stop();
+            }
+        }
+        self.remove_stopped();
+    }
+
+    fn remove_stopped(&mut self) {
         self.playing
-            .retain(|sound| sound.status() == SoundStatus::PLAYING);
+            .retain(|playing| playing.sound.status() == SoundStatus::PLAYING);
     }
 }
 
-#[derive(Hash, Eq, PartialEq, Copy, Clone)]
+#[derive(Hash, Eq, PartialEq, Copy, Clone, Debug)]
 pub enum SoundType {
     Bounce,
+    /// The voice set for balls, if any, or else the bounce sample.
+    Ball,
+    /// The voice set for rings, if any, or else the bounce sample.
+    Ring,
+    /// A ring breaking apart.
+    Shatter,
+    /// A sample from the scene's [`SoundBank`], by its index there.
+    Bank(usize),
 }
 
-#[derive(Default)]
-pub struct SoundList<'s>(HashMap<SoundType, &'s SfBox<SoundBuffer>>);
+impl SoundType {
+    /// The sound types that exist in every scene.
+    pub const ALL: [SoundType; 4] = [
+        SoundType::Bounce,
+        SoundType::Ball,
+        SoundType::Ring,
+        SoundType::Shatter,
+    ];
+
+    /// What the sound type is called in scene files, or `None` for one from
+    /// a [`SoundBank`], which goes by its sample's name.
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            SoundType::Bounce => Some("bounce"),
+            SoundType::Ball => Some("ball"),
+            SoundType::Ring => Some("ring"),
+            SoundType::Shatter => Some("shatter"),
+            SoundType::Bank(_) => None,
+        }
+    }
+
+    /// Name of the bundled sample played when no voice is synthesized for
+    /// this sound, or `None` for one from a [`SoundBank`].
+    pub fn file(self) -> Option<&'static str> {
+        match self {
+            SoundType::Bounce | SoundType::Ball | SoundType::Ring => Some("bounce.wav"),
+            SoundType::Shatter => Some("shatter.wav"),
+            SoundType::Bank(_) => None,
+        }
+    }
+}
+
+/// Samples picked by name from a directory, for sounds assigned to single
+/// objects or pairs of them.
+#[derive(Clone, Debug, Default)]
+pub struct SoundBank {
+    dir: PathBuf,
+    names: Vec<String>,
+}
+
+impl SoundBank {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            names: Vec::new(),
+        }
+    }
+
+    /// The sound that plays `name`, adding it to the bank the first time.
+    pub fn add(&mut self, name: &str) -> SoundType {
+        let index = match self.names.iter().position(|known| known == name) {
+            Some(index) => index,
+            None => {
+                self.names.push(name.to_owned());
+                self.names.len() - 1
+            }
+        };
+        SoundType::Bank(index)
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
 
+    /// Every sound in the bank, in the order they were added.
+    pub fn sounds(&self) -> impl Iterator<Item = SoundType> {
+        (0..self.names.len()).map(SoundType::Bank)
+    }
+
+    /// Where the sample called `name` is: `<name>.wav` in the bank's
+    /// directory, unless the name has an extension of its own.
+    pub fn path(&self, name: &str) -> PathBuf {
+        let path = self.dir.join(name);
+        if path.extension().is_some() {
+            path
+        } else {
+            path.with_extension("wav")
+        }
+    }
+
+    /// The sample file `sound` plays when it isn't synthesized: in the bank,
+    /// or the bundled one found through `paths`.
+    pub fn file(&self, sound: SoundType, paths: &AssetPaths) -> Result<PathBuf, AssetError> {
+        match (sound, sound.file()) {
+            (_, Some(name)) => paths.find(name),
+            (SoundType::Bank(index), None) => Ok(self.path(&self.names[index])),
+            (_, None) => unreachable!("only bank sounds have no bundled file"),
+        }
+    }
+}
+
+/// A sound the simulation asked for, stamped with the simulated time of the
+/// step that triggered it.
+#[derive(Clone, Copy)]
+pub struct SoundTrigger {
+    pub time: f32,
+    pub sound: SoundType,
+    pub pitch: f32,
+    pub volume: f32,
+    /// Stereo position, from -1 for fully left to 1 for fully right.
+    pub pan: f32,
+    /// Seconds after which the sound is cut off, if it should not simply
+    /// play to its end.
+    pub duration: Option<f32>,
+}
+
+/// The buffers to play every sound type from, borrowed from [`Assets`].
+#[cfg(feature = "sfml")]
+pub struct SoundList<'s> {
+    assets: &'s Assets,
+    samples: HashMap<SoundType, &'s SfBox<SoundBuffer>>,
+    voices: Voices,
+    /// Voices rendered so far, by the pitch in cents they were rendered at.
+    rendered: HashMap<(SoundType, i32), &'s SfBox<SoundBuffer>>,
+}
+
+#[cfg(feature = "sfml")]
 impl<'s> SoundList<'s> {
-    pub fn new() -> Self {
-        Self(HashMap::new())
+    pub fn new(assets: &'s Assets) -> Self {
+        Self {
+            assets,
+            samples: HashMap::new(),
+            voices: Voices::default(),
+            rendered: HashMap::new(),
+        }
     }
 
-    pub fn preload(&mut self) {
-        self.0.insert(
-            SoundType::Bounce,
-            Box::leak(Box::new(
-                SoundBuffer::from_file(assets!("bounce.wav")).unwrap(),
-            )),
-        );
+    /// Loads the bundled sample of each of `sounds` without a voice, such as
+    /// the ones [`Simulation::sampled_sounds`](crate::sim::Simulation::sampled_sounds)
+    /// says a run can play.
+    pub fn preload(&mut self, sounds: &[SoundType]) -> Result<(), AssetError> {
+        for &sound in sounds {
+            if let (None, Some(name)) = (self.voices.get(sound), sound.file()) {
+                let path = self.assets.paths().find(name)?;
+                self.load(sound, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(&mut self, soundtype: SoundType, path: &Path) -> Result<(), AssetError> {
+        let buffer = self.assets.sound(path)?;
+        self.samples.insert(soundtype, buffer);
+        Ok(())
+    }
+
+    /// Loads every sample in `bank`.
+    pub fn load_bank(&mut self, bank: &SoundBank) -> Result<(), AssetError> {
+        for sound in bank.sounds() {
+            self.load(sound, &bank.file(sound, self.assets.paths())?)?;
+        }
+        Ok(())
+    }
+
+    /// Synthesizes `voices` instead of playing samples for the sound types
+    /// they are set for.
+    pub fn set_voices(&mut self, voices: Voices) {
+        self.voices = voices;
+        self.rendered.clear();
     }
 
-    #[allow(dead_code)]
-    pub fn load(&mut self, soundtype: SoundType, filename: &str) {
-        self.0.insert(
-            soundtype,
-            Box::leak(Box::new(SoundBuffer::from_file(filename).unwrap())),
-        );
+    pub fn get(&self, soundtype: SoundType) -> Option<&'s SfBox<SoundBuffer>> {
+        self.samples.get(&soundtype).copied()
     }
 
-    pub fn get(&self, soundtype: SoundType) -> &'s SfBox<SoundBuffer> {
-        self.0.get(&soundtype).unwrap()
+    /// Renders every voice at each of `pitches` now, so they don't have to be
+    /// synthesized while the simulation is running.
+    pub fn prerender(&mut self, pitches: &[f32]) {
+        for sound in SoundType::ALL {
+            if self.voices.get(sound).is_none() {
+                continue;
+            }
+            for cents in pitches.iter().filter_map(|&pitch| pitch_in_cents(pitch)) {
+                self.rendered(sound, cents);
+            }
+        }
+    }
+
+    /// The buffer to play `soundtype` at `pitch` from, and the pitch to set
+    /// on it. Voices are rendered at `pitch` to the nearest cent and played
+    /// as they are, like in a mixdown; samples are repitched. `None` if the
+    /// sound isn't loaded or a voice can't play `pitch`.
+    pub fn get_pitched(
+        &mut self,
+        soundtype: SoundType,
+        pitch: f32,
+    ) -> Option<(&'s SfBox<SoundBuffer>, f32)> {
+        if self.voices.get(soundtype).is_none() {
+            return Some((self.get(soundtype)?, pitch));
+        }
+        let buffer = self.rendered(soundtype, pitch_in_cents(pitch)?)?;
+        Some((buffer, 1.0))
+    }
+
+    /// The voice of `soundtype` rendered at `cents`, rendering it first if it
+    /// hasn't been yet.
+    fn rendered(&mut self, soundtype: SoundType, cents: i32) -> Option<&'s SfBox<SoundBuffer>> {
+        if let Some(&buffer) = self.rendered.get(&(soundtype, cents)) {
+            return Some(buffer);
+        }
+        let samples: Vec<i16> = self
+            .voices
+            .get(soundtype)?
+            .render(cents_to_pitch(cents), VOICE_SAMPLE_RATE)
+            .into_iter()
+            .map(|sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+        let buffer = SoundBuffer::from_samples(&samples, 1, VOICE_SAMPLE_RATE).ok()?;
+        let buffer = self.assets.add_sound(buffer);
+        self.rendered.insert((soundtype, cents), buffer);
+        Some(buffer)
     }
 }
+
+#[cfg(feature = "sfml")]
+const VOICE_SAMPLE_RATE: u32 = 44100;
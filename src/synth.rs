@@ -0,0 +1,236 @@
+use std::f32::consts::TAU;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::sounds::SoundType;
+
+/// The raw waveform of a [`Voice`], before its envelope is applied.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Tone {
+    Sine,
+    Square,
+    Saw,
+    Triangle,
+    Noise,
+    /// A sine carrier phase-modulated by a second sine at `ratio` times its
+    /// frequency; a higher `index` gives a brighter, more metallic sound.
+    Fm {
+        ratio: f32,
+        index: f32,
+    },
+    /// A Karplus-Strong plucked string. `damping` is the share of energy
+    /// lost on every trip along the string.
+    Pluck {
+        #[serde(default = "default_damping")]
+        damping: f32,
+    },
+}
+
+/// Attack, decay and release in seconds, sustain as a level from 0 to 1.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Envelope {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self {
+            attack: 0.005,
+            decay: 0.15,
+            sustain: 0.4,
+            release: 0.25,
+        }
+    }
+}
+
+impl Envelope {
+    /// Level `t` seconds into a note held for `gate` seconds.
+    fn level(&self, t: f32, gate: f32) -> f32 {
+        if t >= gate {
+            let released = if self.release > 0.0 {
+                1.0 - (t - gate) / self.release
+            } else {
+                0.0
+            };
+            return self.level(gate, f32::INFINITY) * released.max(0.0);
+        }
+        if t < self.attack {
+            t / self.attack
+        } else if t < self.attack + self.decay {
+            1.0 - (1.0 - self.sustain) * (t - self.attack) / self.decay
+        } else {
+            self.sustain
+        }
+    }
+}
+
+/// A synthesized sound: a [`Tone`] shaped by an [`Envelope`].
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Voice {
+    pub tone: Tone,
+    #[serde(default)]
+    pub envelope: Envelope,
+    /// Hz played at pitch 1. Defaults to middle C, the key melodies expect
+    /// unpitched sounds to be in.
+    #[serde(default = "default_frequency")]
+    pub frequency: f32,
+    /// Seconds a note is held before its release starts.
+    #[serde(default = "default_length")]
+    pub length: f32,
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+}
+
+impl Voice {
+    pub fn new(tone: Tone) -> Self {
+        Self {
+            tone,
+            envelope: Envelope::default(),
+            frequency: default_frequency(),
+            length: default_length(),
+            gain: default_gain(),
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.frequency <= 0.0 || !self.frequency.is_finite() {
+            return Err(String::from("frequency must be greater than 0"));
+        }
+        if self.length < 0.0 || !self.length.is_finite() {
+            return Err(String::from("length must not be negative"));
+        }
+        if self.gain < 0.0 || !self.gain.is_finite() {
+            return Err(String::from("gain must not be negative"));
+        }
+        let Envelope {
+            attack,
+            decay,
+            sustain,
+            release,
+        } = self.envelope;
+        if [attack, decay, release]
+            .iter()
+            .any(|time| *time < 0.0 || !time.is_finite())
+        {
+            return Err(String::from("envelope times must not be negative"));
+        }
+        if !(0.0..=1.0).contains(&sustain) {
+            return Err(String::from("envelope sustain must be between 0 and 1"));
+        }
+        match self.tone {
+            Tone::Pluck { damping } if !(0.0..=1.0).contains(&damping) => {
+                return Err(String::from("pluck damping must be between 0 and 1"));
+            }
+            Tone::Fm { ratio, .. } if ratio <= 0.0 || !ratio.is_finite() => {
+                return Err(String::from("fm ratio must be greater than 0"));
+            }
+            Tone::Fm { index, .. } if index < 0.0 || !index.is_finite() => {
+                return Err(String::from("fm index must not be negative"));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Renders one note at `pitch` times the voice's frequency as mono
+    /// samples in `[-1, 1]`, computed at that exact frequency rather than
+    /// resampled.
+    pub fn render(&self, pitch: f32, sample_rate: u32) -> Vec<f32> {
+        let frequency = self.frequency * pitch;
+        let rate = sample_rate as f32;
+        let length = ((self.length + self.envelope.release) * rate).ceil() as usize;
+        let mut noise = StdRng::seed_from_u64(0);
+
+        let mut samples: Vec<f32> = match self.tone {
+            Tone::Pluck { damping } => {
+                let period = ((rate / frequency).round() as usize).max(2);
+                let mut string: Vec<f32> =
+                    (0..period).map(|_| noise.gen_range(-1.0..1.0)).collect();
+                (0..length)
+                    .map(|i| {
+                        let (here, next) = (i % period, (i + 1) % period);
+                        let sample = string[here];
+                        string[here] = (string[here] + string[next]) * 0.5 * (1.0 - damping);
+                        sample
+                    })
+                    .collect()
+            }
+            tone => (0..length)
+                .map(|i| {
+                    let t = i as f32 / rate;
+                    let phase = (t * frequency).fract();
+                    match tone {
+                        Tone::Sine => (TAU * phase).sin(),
+                        Tone::Square if phase < 0.5 => 1.0,
+                        Tone::Square => -1.0,
+                        Tone::Saw => 2.0 * phase - 1.0,
+                        Tone::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+                        Tone::Noise => noise.gen_range(-1.0..1.0),
+                        Tone::Fm { ratio, index } => {
+                            let modulator = (TAU * frequency * ratio * t).sin();
+                            (TAU * phase + index * modulator).sin()
+                        }
+                        Tone::Pluck { .. } => unreachable!(),
+                    }
+                })
+                .collect(),
+        };
+
+        for (i, sample) in samples.iter_mut().enumerate() {
+            *sample *= self.gain * self.envelope.level(i as f32 / rate, self.length);
+        }
+        samples
+    }
+}
+
+/// The voices played instead of the bounce sample, per kind of object.
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Voices {
+    pub ball: Option<Voice>,
+    pub ring: Option<Voice>,
+}
+
+impl Voices {
+    pub fn get(&self, sound: SoundType) -> Option<&Voice> {
+        match sound {
+            SoundType::Bounce | SoundType::Shatter | SoundType::Bank(_) => None,
+            SoundType::Ball => self.ball.as_ref(),
+            SoundType::Ring => self.ring.as_ref(),
+        }
+    }
+}
+
+/// `pitch` rounded to whole cents, so notes that sound the same share one
+/// rendered buffer. `None` for pitches that can't be played.
+pub fn pitch_in_cents(pitch: f32) -> Option<i32> {
+    (pitch > 0.0).then(|| (1200.0 * pitch.log2()).round() as i32)
+}
+
+/// The pitch [`pitch_in_cents`] stands for.
+pub fn cents_to_pitch(cents: i32) -> f32 {
+    2f32.powf(cents as f32 / 1200.0)
+}
+
+fn default_damping() -> f32 {
+    0.004
+}
+
+fn default_frequency() -> f32 {
+    261.63
+}
+
+fn default_length() -> f32 {
+    0.2
+}
+
+fn default_gain() -> f32 {
+    0.5
+}
@@ -0,0 +1,1007 @@
+use std::{
+    f32::consts::{FRAC_1_SQRT_2, PI, SQRT_2, TAU},
+    path::{Path, PathBuf},
+};
+
+use collide_and_sound::{
+    assets::{AssetError, AssetPaths, Store},
+    ball::{Ball, BallSize},
+    ending::Outcome,
+    entity::Entity,
+    melody::{Melody, MelodyEnd},
+    mixdown::{render_wav, Mixdown, Samples},
+    motion::{Keyframe, Motion, Pose},
+    music::{Scale, Tuning},
+    physics::DEFAULT_GRAVITY,
+    polyphony::{Allocation, PlayingVoice, Polyphony, StealPolicy},
+    render::Color,
+    ring::{Gap, Ring},
+    scene::{Scene, SceneError},
+    sim::{RunLimit, RunReport, Simulation},
+    sounds::{SoundTrigger, SoundType},
+    synth::{Envelope, Tone, Voice},
+};
+use rapier2d::math::Vector;
+
+/// A small ball in the middle of a large ring, with more keys for each
+/// added after their `position` and `size`. Tables for the ring, such as
+/// `[[ring.gap]]`, and top-level ones go at the end of `ring`.
+fn ball_in_ring(ball: &str, ring: &str) -> String {
+    format!(
+        "[[ball]]\nposition = [320.0, 180.0]\nsize = \"small\"\n{ball}\n\
+         [[ring]]\nposition = [320.0, 180.0]\nsize = \"large\"\n{ring}\n"
+    )
+}
+
+/// Runs the scene in `source` with seed 1 for up to `seconds`.
+fn run(source: &str, seconds: f32) -> (Simulation, RunReport) {
+    let mut sim = Simulation::new(&Scene::from_toml(source).unwrap(), 1);
+    let report = sim.run_headless(RunLimit::Seconds(seconds));
+    (sim, report)
+}
+
+fn collision_log(seed: u64) -> Vec<String> {
+    let mut sim = Simulation::new(&Scene::default(), seed);
+    let mut log = Vec::new();
+    for _ in 0..900 {
+        sim.step();
+        log.extend(
+            sim.take_collisions()
+                .iter()
+                .map(|c| format!("{} {:?} {:?} {:?}", c.time, c.first, c.second, c.impact)),
+        );
+    }
+    log
+}
+
+#[test]
+fn same_seed_replays_identically() {
+    let first = collision_log(7);
+    assert!(!first.is_empty());
+    assert_eq!(first, collision_log(7));
+}
+
+#[test]
+fn scene_errors_name_the_problem() {
+    let err = Scene::from_toml("[[ball]]\nposition = [0.0, 0.0]\n")
+        .err()
+        .unwrap();
+    assert!(matches!(err, SceneError::Invalid { .. }));
+    assert!(err.to_string().contains("ball 0"));
+    for (shape, problem) in [
+        ("radius = nan", "radius"),
+        ("radius = inf", "radius"),
+        ("radius = 5.0\noutline_thickness = nan", "outline_thickness"),
+    ] {
+        let err = Scene::from_toml(&format!("[[ball]]\nposition = [0.0, 0.0]\n{shape}\n"))
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains(&format!("ball 0: {problem} must be greater than 0")));
+    }
+    for rate in ["0.0", "nan", "inf"] {
+        let err = Scene::from_toml(&format!("physics_rate = {rate}\n"))
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains("physics_rate must be greater than 0"));
+    }
+}
+
+#[test]
+fn pair_selectors_must_exist() {
+    let err = Scene::from_toml(
+        "[[pair]]\nbetween = [\"ball:3\", \"ring\"]\nrules = [{ kind = \"none\" }]\n",
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("pair 0: there is no ball 3"));
+}
+
+#[test]
+fn tuned_pitches_land_on_scale_notes() {
+    let tuning = Tuning {
+        scale: Scale::Major,
+        root: 0,
+        octaves: [0, 1],
+    };
+    assert_eq!(tuning.pitch(0.0), 1.0);
+    assert_eq!(tuning.pitch(1.0), 2.0);
+    assert_eq!(tuning.pitch(0.5), 2f32.powf(7.0 / 12.0));
+}
+
+#[test]
+fn melody_plays_midi_notes_in_order() {
+    let mut melody = Melody::from_midi("scenes/twinkle.mid", 0).unwrap();
+    melody.set_end(MelodyEnd::Stop);
+    let keys: Vec<u8> = std::iter::from_fn(|| melody.next_note(100.0))
+        .map(|note| note.key)
+        .collect();
+    assert_eq!(&keys[..7], &[60, 60, 67, 67, 69, 69, 67]);
+    assert_eq!(keys.len(), melody.notes().len());
+    assert!((melody.notes()[0].duration - 0.45).abs() < 1e-4);
+}
+
+#[test]
+fn full_polyphony_steals_by_policy() {
+    let scene = Scene::from_toml(
+        "[polyphony]\nmax_voices = 2\nsteal = \"priority\"\n\
+         limits = { ring = 1 }\npriority = { ring = 1 }\n",
+    )
+    .unwrap();
+    let polyphony = scene.polyphony;
+    let voice = |sound, volume| PlayingVoice { sound, volume };
+    let playing = [voice(SoundType::Ring, 1.0), voice(SoundType::Bounce, 0.5)];
+
+    assert_eq!(
+        polyphony.allocate(&playing[..1], SoundType::Bounce, 1.0),
+        Allocation::Play
+    );
+    assert_eq!(
+        polyphony.allocate(&playing, SoundType::Ring, 1.0),
+        Allocation::Steal(0)
+    );
+    assert_eq!(
+        polyphony.allocate(&playing, SoundType::Bounce, 1.0),
+        Allocation::Steal(1)
+    );
+    let full = [voice(SoundType::Bounce, 0.5), voice(SoundType::Ring, 1.0)];
+    let mut quiet = polyphony.clone();
+    quiet.steal = StealPolicy::Quietest;
+    assert_eq!(
+        quiet.allocate(&full, SoundType::Bounce, 0.2),
+        Allocation::Drop
+    );
+
+    // Bank sounds are limited and prioritised by their sample's name.
+    let source = |limited: &str| {
+        format!(
+            "[polyphony]\nsteal = \"priority\"\nlimits = {{ {limited} = 1 }}\n\
+             priority = {{ thud = 2 }}\n\
+             [sounds]\nbank = \"scenes/sounds\"\n\
+             [[ball]]\nposition = [320.0, 180.0]\nsize = \"small\"\nsound = \"block\"\n\
+             [[ring]]\nposition = [320.0, 180.0]\nsize = \"large\"\nsound = \"thud\"\n"
+        )
+    };
+    let scene = Scene::from_toml(&source("block")).unwrap();
+    let mut polyphony = Simulation::new(&scene, 1).polyphony().clone();
+    let (block, thud) = (SoundType::Bank(0), SoundType::Bank(1));
+    let playing = [voice(thud, 1.0), voice(block, 1.0)];
+    assert_eq!(
+        polyphony.allocate(&playing, block, 1.0),
+        Allocation::Steal(1)
+    );
+    assert_eq!(
+        polyphony.allocate(&playing[..1], block, 1.0),
+        Allocation::Play
+    );
+    polyphony.max_voices = 2;
+    assert_eq!(
+        polyphony.allocate(&playing, SoundType::Bounce, 1.0),
+        Allocation::Steal(1)
+    );
+    let err = Scene::from_toml(&source("knock")).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "invalid scene <inline>: polyphony: there is no sound `knock`"
+    );
+}
+
+#[test]
+fn bank_sounds_follow_objects_and_pairs() {
+    let err = Scene::from_toml("[[ball]]\nposition = [0.0, 0.0]\nradius = 5.0\nsound = \"nope\"\n")
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("sound `nope` not found"));
+
+    let scene = Scene::load("scenes/soundbank.toml").unwrap();
+    let mut sim = Simulation::new(&scene, 1);
+    let bank = sim.sound_bank().clone();
+    assert_eq!(bank.names(), ["block", "thud", "click"]);
+    let mut played = Vec::new();
+    for _ in 0..900 {
+        sim.step();
+        played.extend(
+            sim.take_sound_triggers()
+                .into_iter()
+                .map(|trigger| trigger.sound),
+        );
+    }
+    assert!(played
+        .iter()
+        .all(|sound| matches!(sound, SoundType::Bank(_))));
+    assert!(played.contains(&SoundType::Bank(0)));
+    assert!(played.contains(&SoundType::Bank(1)));
+}
+
+#[test]
+fn assets_are_searched_in_order_and_missing_ones_named() {
+    let paths = AssetPaths::new(vec![PathBuf::from("nowhere"), PathBuf::from("assets")]);
+    assert_eq!(
+        paths.find("bounce.wav").unwrap(),
+        Path::new("assets/bounce.wav")
+    );
+    let err = paths.find("missing.wav").unwrap_err();
+    assert!(matches!(err, AssetError::NotFound { .. }));
+    assert_eq!(
+        err.to_string(),
+        "asset missing.wav not found in nowhere, assets"
+    );
+
+    let store = Store::new();
+    let path = Path::new("assets/bounce.wav");
+    let size = *store
+        .get_or_load(path, |path| Ok(std::fs::metadata(path).unwrap().len()))
+        .unwrap();
+    assert!(size > 0 && store.is_loaded(path));
+    assert!(!store.is_loaded(Path::new("assets/missing.wav")));
+}
+
+#[test]
+fn impacts_point_from_first_to_second() {
+    let mut sim = Simulation::new(&Scene::default(), 1);
+    let center = sim.rings()[0].position();
+    let mut ring_hits = 0;
+    for _ in 0..600 {
+        sim.step();
+        for collision in sim.take_collisions() {
+            let impact = collision.impact;
+            assert!((impact.normal.norm() - 1.0).abs() < 1e-3);
+            assert!(impact.strength > 0.0 && impact.speed > 0.0);
+            let outward = (impact.point - center).dot(&impact.normal);
+            match (collision.first, collision.second) {
+                (Entity::Ball(_), Entity::Ring(_)) => assert!(outward > 0.0),
+                (Entity::Ring(_), Entity::Ball(_)) => assert!(outward < 0.0),
+                _ => continue,
+            }
+            ring_hits += 1;
+        }
+    }
+    assert!(ring_hits > 0);
+}
+
+#[test]
+fn contact_forces_are_reported_only_past_resting_weight() {
+    let threshold = |gravity: Vector<f32>| {
+        Ball::new_with_size([0.0, 0.0], BallSize::Small)
+            .create_collider(gravity)
+            .contact_force_event_threshold()
+    };
+    let default = threshold(Vector::new(0.0, DEFAULT_GRAVITY));
+    assert!(default > 0.0);
+    assert_eq!(threshold(Vector::zeros()), default);
+    assert_eq!(threshold(Vector::new(DEFAULT_GRAVITY, 0.0)), default);
+    assert_eq!(
+        threshold(Vector::new(0.0, 2.0 * DEFAULT_GRAVITY)),
+        2.0 * default
+    );
+    assert_eq!(Scene::default().gravity, [0.0, DEFAULT_GRAVITY]);
+}
+
+#[test]
+fn balls_escape_only_through_gaps() {
+    let escapes = |gap: &str| run(&ball_in_ring("", gap), 5.0).0.take_escapes();
+    assert!(escapes("").is_empty());
+    let below = escapes("[[ring.gap]]\nstart = 60.0\nlength = 60.0");
+    assert_eq!(below.len(), 1);
+    assert_eq!((below[0].ball, below[0].ring), (0, 0));
+
+    let err = Scene::from_toml(&ball_in_ring(
+        "",
+        "[[ring.gap]]\nstart = 0.0\nlength = 360.0",
+    ))
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "invalid scene <inline>: ring 0: gap 0: length must be between 0 and 360 degrees"
+    );
+
+    let mut ring = Ring::new([0.0, 0.0]);
+    ring.set_gaps(vec![
+        Gap {
+            start: 5.5,
+            length: 1.5,
+        },
+        Gap {
+            start: 0.2,
+            length: 0.3,
+        },
+        Gap {
+            start: 1.0,
+            length: 1.0,
+        },
+    ]);
+    let arcs = ring.solid_arcs();
+    assert_eq!(arcs.len(), 2);
+    assert!((arcs[0].0 - (7.0 - TAU)).abs() < 1e-5 && (arcs[0].1 - (1.0 - arcs[0].0)).abs() < 1e-5);
+    assert!((arcs[1].0 - 2.0).abs() < 1e-5 && (arcs[1].1 - 3.5).abs() < 1e-5);
+}
+
+#[test]
+fn spinning_rings_carry_balls_along() {
+    let spin = |motion: &str| {
+        let (sim, _) = run(
+            &ball_in_ring("rules = []", &format!("rules = []\n{motion}")),
+            2.0,
+        );
+        (sim.balls()[0].position().x, sim.rings()[0].angle())
+    };
+    let (still_x, still_angle) = spin("");
+    assert!((still_x - 320.0).abs() < 10.0 && still_angle == 0.0);
+    let (spun_x, spun_angle) = spin("[ring.motion]\nkind = \"spin\"\nspeed = 90.0");
+    assert!((spun_angle - PI).abs() < 1e-3);
+    assert!(spun_x < 290.0, "ball stayed at x = {spun_x}");
+
+    let path = Motion::Path {
+        keyframes: vec![Keyframe {
+            time: 2.0,
+            pose: Pose {
+                offset: Vector::new(10.0, 0.0),
+                angle: 1.0,
+            },
+        }],
+        repeat: true,
+    };
+    assert_eq!(path.pose(0.5).offset.x, 2.5);
+    assert_eq!(path.pose(2.5).angle, 0.25);
+
+    for (motion, problem) in [
+        ("kind = \"spin\"\nspeed = nan", "speed must be finite"),
+        (
+            "kind = \"oscillate\"\nperiod = inf",
+            "period must be greater than 0",
+        ),
+        (
+            "kind = \"oscillate\"\nperiod = 1.0\namplitude = nan",
+            "amplitude and offset must be finite",
+        ),
+        (
+            "kind = \"path\"\nkeyframes = [{ time = nan }]",
+            "keyframe 0: time must be later",
+        ),
+        (
+            "kind = \"path\"\nkeyframes = [{ time = 1.0, offset = [inf, 0.0] }]",
+            "keyframe 0: offset and angle must be finite",
+        ),
+    ] {
+        let err = Scene::from_toml(&ball_in_ring("", &format!("[ring.motion]\n{motion}")))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains(problem), "{err}");
+    }
+}
+
+#[test]
+fn rings_break_after_their_last_hit() {
+    let source = "[[ball]]\nposition = [320.0, 180.0]\nsize = \"small\"\nrules = []\n\
+                  [concentric]\nposition = [320.0, 180.0]\ncount = 3\ninner_radius = 100.0\n\
+                  spacing = 40.0\nhealth = 2\nrules = []\n";
+    let scene = Scene::from_toml(source).unwrap();
+    let radii: Vec<_> = scene.rings.iter().map(|ring| ring.radius).collect();
+    assert_eq!(radii, [Some(100.0), Some(140.0), Some(180.0)]);
+
+    let mut sim = Simulation::new(&scene, 1);
+    let mut hits = [0; 3];
+    let mut broken = Vec::new();
+    for _ in 0..600 {
+        sim.step();
+        for collision in sim.take_collisions() {
+            if let Entity::Ring(ring) = collision.second {
+                hits[ring] += 1;
+            }
+        }
+        for ring in sim.take_breaks().iter().map(|b| b.ring) {
+            assert_eq!(hits[ring], 2, "ring {ring} broke after {} hits", hits[ring]);
+            broken.push(ring);
+        }
+    }
+    assert_eq!(broken, [0, 1, 2]);
+    assert!(sim
+        .rings()
+        .iter()
+        .all(|ring| ring.is_broken() && ring.rb_handle().is_none()));
+    assert!(sim
+        .take_sound_triggers()
+        .iter()
+        .any(|t| t.sound == SoundType::Shatter));
+    assert!(sim.balls()[0].position().y > 400.0);
+
+    let err = Scene::from_toml(&source.replace("spacing = 40.0", "spacing = 5.0"))
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("concentric: spacing"));
+    for (from, to, problem) in [
+        ("inner_radius = 100.0", "inner_radius = nan", "inner_radius"),
+        ("spacing = 40.0", "spacing = inf", "spacing"),
+        (
+            "spacing = 40.0",
+            "spacing = 40.0\noutline_thickness = nan",
+            "outline_thickness",
+        ),
+    ] {
+        let err = Scene::from_toml(&source.replace(from, to)).err().unwrap();
+        assert!(
+            err.to_string().contains(&format!("concentric: {problem}")),
+            "{err}"
+        );
+    }
+}
+
+#[test]
+fn spawning_rules_stop_at_the_ball_cap() {
+    let source = |max_balls: usize| {
+        format!(
+            "max_balls = {max_balls}\n{}",
+            ball_in_ring(
+                "velocity = [60.0, 0.0]\nrules = []",
+                "rules = [{ kind = \"spawn\", every = 2 }]"
+            )
+        )
+    };
+    let mut sim = Simulation::new(&Scene::from_toml(&source(100)).unwrap(), 1);
+    let mut ring_hits = 0;
+    for _ in 0..300 {
+        sim.step();
+        ring_hits += sim
+            .take_collisions()
+            .iter()
+            .filter(|c| matches!(c.second, Entity::Ring(_)) || matches!(c.first, Entity::Ring(_)))
+            .count();
+        assert_eq!(sim.balls().len(), 1 + ring_hits / 2);
+    }
+    assert!(sim.balls().len() > 1);
+
+    let (capped, _) = run(&source(3), 10.0);
+    assert_eq!(capped.balls().len(), 3);
+
+    let err = Scene::from_toml(&source(3).replace("every = 2", "every = 0"))
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("spawn rule needs `every`"));
+}
+
+#[test]
+fn split_balls_stay_whole_without_room_for_the_other_half() {
+    let source = |max_balls: usize| {
+        format!(
+            "max_balls = {max_balls}\n{}",
+            ball_in_ring(
+                "rules = [{ kind = \"split\", min_radius = 4.0 }]",
+                "rules = []"
+            )
+        )
+    };
+    let (_, report) = run(&source(1), 3.0);
+    assert_eq!(report.balls.len(), 1);
+    assert_eq!(report.balls[0].radius, 15.0);
+
+    let (_, report) = run(&source(2), 3.0);
+    assert_eq!(report.balls.len(), 2);
+    assert!(report
+        .balls
+        .iter()
+        .all(|ball| (ball.radius - 15.0 * FRAC_1_SQRT_2).abs() < 1e-4));
+
+    // A ball grazing the wall splits into halves beside each other along it,
+    // not pushed into it.
+    let grazing = "gravity = [0.0, 0.0]\nmax_balls = 2\n\
+                   [[ball]]\nposition = [320.0, 295.0]\nradius = 20.0\nvelocity = [300.0, 10.0]\n\
+                   rules = [{ kind = \"split\", min_radius = 4.0 }]\n\
+                   [[ring]]\nposition = [320.0, 180.0]\nsize = \"large\"\nrules = []\n";
+    let mut sim = Simulation::new(&Scene::from_toml(grazing).unwrap(), 1);
+    while sim.balls().len() < 2 {
+        assert!(sim.time() < 2.0, "nothing was split");
+        sim.step();
+    }
+    let ring = &sim.rings()[0];
+    for ball in sim.balls() {
+        let reach =
+            (ball.position() - ring.position()).norm() + ball.radius() + ball.outline_thickness();
+        assert!(
+            reach < ring.radius(),
+            "half reaches {reach} from the center"
+        );
+    }
+}
+
+#[test]
+fn rule_sizes_must_be_positive() {
+    let error = |rule: &str| {
+        Scene::from_toml(&ball_in_ring(&format!("rules = [{rule}]"), ""))
+            .err()
+            .map(|err| err.to_string())
+    };
+    assert_eq!(error("{ kind = \"shrink\" }"), None);
+    assert_eq!(error("{ kind = \"split\", min_radius = 4.0 }"), None);
+    for value in ["0.0", "-2.0", "nan", "inf"] {
+        for rule in [
+            format!("{{ kind = \"split\", min_radius = {value} }}"),
+            format!("{{ kind = \"shrink\", min_radius = {value} }}"),
+        ] {
+            assert_eq!(
+                error(&rule).as_deref(),
+                Some("invalid scene <inline>: ball 0: rule min_radius must be greater than 0"),
+                "{rule}"
+            );
+        }
+        let factor = error(&format!("{{ kind = \"grow\", factor = {value} }}"));
+        assert!(factor
+            .unwrap()
+            .contains("rule factor must be greater than 0"));
+    }
+    for value in ["-1.0", "nan", "inf"] {
+        let rule = format!("{{ kind = \"restitution\", value = {value} }}");
+        assert!(error(&rule)
+            .unwrap()
+            .contains("restitution must not be negative"));
+    }
+    for threshold in ["-1.0", "nan"] {
+        let rule = format!("{{ kind = \"split\", min_radius = 4.0, threshold = {threshold} }}");
+        assert!(error(&rule)
+            .unwrap()
+            .contains("split threshold must not be negative"));
+    }
+}
+
+#[test]
+fn duplicates_start_beside_their_ball_and_move_their_own_way() {
+    let source = format!(
+        "gravity = [0.0, 0.0]\n{}\n\
+         [[pair]]\nbetween = [\"ball:0\", \"ring\"]\napply_to = \"first\"\n\
+         rules = [{{ kind = \"duplicate\" }}]",
+        ball_in_ring("velocity = [200.0, 0.0]\nrules = []", "rules = []")
+    );
+    let mut sim = Simulation::new(&Scene::from_toml(&source).unwrap(), 1);
+    while sim.balls().len() < 2 {
+        assert!(sim.time() < 2.0, "nothing was duplicated");
+        sim.step();
+    }
+    let [ball, copy] = [0, 1].map(|i| sim.balls()[i].position());
+    let reach = 2.0 * (15.0 + 5.0);
+    assert!(
+        (copy - ball).norm() >= reach - 0.5,
+        "copy {copy} too close to {ball}"
+    );
+
+    sim.step();
+    let moved = |i: usize, from: Vector<f32>| sim.balls()[i].position() - from;
+    assert!((moved(1, copy) - moved(0, ball)).norm() > 1.0);
+}
+
+#[test]
+fn merged_balls_keep_their_area_and_momentum() {
+    let source = "gravity = [0.0, 0.0]\n\
+                  [[ball]]\nposition = [200.0, 180.0]\nradius = 10.0\noutline_thickness = 1.0\n\
+                  velocity = [100.0, 0.0]\nrules = [{ kind = \"merge\" }]\n\
+                  [[ball]]\nposition = [300.0, 180.0]\nradius = 5.0\noutline_thickness = 1.0\n\
+                  velocity = [-50.0, 0.0]\nrules = []\n";
+    let (mut sim, report) = run(source, 2.0);
+    let merges = sim.take_merges();
+    assert_eq!(merges.len(), 1);
+    assert_eq!((merges[0].ball, merges[0].absorbed), (0, 1));
+    assert!(sim.balls()[1].is_absorbed() && sim.balls()[1].rb_handle().is_none());
+    assert_eq!((sim.balls().len(), sim.live_balls()), (2, 1));
+    assert_eq!(report.balls.len(), 1);
+    assert!((report.balls[0].radius - 125f32.sqrt()).abs() < 1e-4);
+
+    // Masses go with the collider's area, outline included.
+    let expected = (121.0 * 100.0 - 36.0 * 50.0) / 157.0;
+    let before = sim.balls()[0].position().x;
+    sim.run_headless(RunLimit::Seconds(1.0));
+    let speed = sim.balls()[0].position().x - before;
+    assert!((speed - expected).abs() < 0.5, "moved {speed} in a second");
+}
+
+#[test]
+fn runs_end_at_the_first_condition_met() {
+    let scene = |ends: &str| {
+        ball_in_ring(
+            "",
+            &format!("[[ring.gap]]\nstart = 60.0\nlength = 60.0\n{ends}"),
+        )
+    };
+    let end = |ends: &str| run(&scene(ends), 10.0).1;
+
+    let report = end(
+        "[[end]]\nkind = \"escaped\"\n[[end]]\nkind = \"time\"\nseconds = 10.0\noutcome = \"lose\"",
+    );
+    let ending = report.ending.unwrap();
+    assert_eq!(ending.outcome(), Outcome::Win);
+    assert_eq!(report.escapes, 1);
+    assert_eq!(report.simulated_seconds, ending.time);
+    assert!(report.simulated_seconds < 5.0);
+
+    // With the gap on top the ball bounces instead of falling out.
+    let source = scene("[[end]]\nkind = \"collisions\"\ncount = 1\noutcome = \"lose\"");
+    let (_, report) = run(&source.replace("start = 60.0", "start = 240.0"), 10.0);
+    let ending = report.ending.unwrap();
+    assert_eq!(ending.outcome(), Outcome::Lose);
+    assert_eq!(report.collisions, 1);
+    assert!(report.to_string().contains("ended: lose at"));
+
+    assert!(end("").ending.is_none());
+    let err = Scene::from_toml(&scene("[[end]]\nkind = \"balls\"\ncount = 0"))
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "invalid scene <inline>: end 0: count must be greater than 0"
+    );
+    for fraction in ["0.0", "1.5", "nan", "inf"] {
+        let err = Scene::from_toml(&scene(&format!(
+            "[[end]]\nkind = \"fill\"\nfraction = {fraction}"
+        )))
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "invalid scene <inline>: end 0: fraction must be greater than 0 and at most 1"
+        );
+    }
+}
+
+#[test]
+fn balls_can_be_added_dragged_flung_and_removed() {
+    let mut sim = Simulation::new(&Scene::default(), 1);
+    sim.step();
+    let ball = sim.balls()[0].position();
+    assert_eq!(sim.ball_at(ball + Vector::new(5.0, 5.0)), Some(0));
+    assert_eq!(sim.ball_at(Vector::new(320.0, 300.0)), None);
+
+    // Dragged to the right for a while, then let go.
+    sim.grab_ball(0, ball);
+    for step in 1..=20 {
+        sim.drag_to(ball + Vector::new(2.0 * step as f32, 0.0));
+        sim.step();
+    }
+    let dragged = sim.balls()[0].position();
+    assert!((dragged - (ball + Vector::new(40.0, 0.0))).norm() < 1.0);
+    sim.release_ball();
+    sim.step();
+    let flung = (sim.balls()[0].position() - dragged) / sim.timestep();
+    assert!((flung.x - 120.0).abs() < 20.0, "flung at {flung}");
+
+    let added = sim
+        .add_ball(Ball::new_with_size([320.0, 120.0], BallSize::Small))
+        .unwrap();
+    assert_eq!(added, 2);
+    // Colors picked for added balls come from the seed, like everything else
+    // random in a run, and one already picked is kept.
+    let added_color = || {
+        let mut sim = Simulation::new(&Scene::default(), 1);
+        let mut ball = Ball::new_with_size([320.0, 120.0], BallSize::Small);
+        ball.rand_outline_color(sim.rng());
+        let ball = sim.add_ball(ball);
+        sim.balls()[ball.unwrap()].outline_color()
+    };
+    assert_eq!(added_color(), added_color());
+    let mut red = Ball::new_with_size([320.0, 240.0], BallSize::Small);
+    red.set_outline_color(Color::rgb(255, 0, 0));
+    let mut other = Simulation::new(&Scene::default(), 1);
+    let red = other.add_ball(red).unwrap();
+    assert_eq!(other.balls()[red].outline_color(), Color::rgb(255, 0, 0));
+    assert!(sim.remove_ball(1));
+    assert!(!sim.remove_ball(1));
+    assert_eq!((sim.balls().len(), sim.live_balls()), (3, 2));
+    sim.run_headless(RunLimit::Seconds(1.0));
+    let left: Vec<_> = sim.report().balls.iter().map(|ball| ball.index).collect();
+    assert_eq!(left, [0, 2]);
+}
+
+#[test]
+fn collisions_name_the_ball_added_after_one_was_removed() {
+    // One ball heads for the left of the ring, the other for its right.
+    let source = "gravity = [0.0, 0.0]\n\
+                  [[ball]]\nposition = [250.0, 180.0]\nsize = \"small\"\nvelocity = [-200.0, 0.0]\n\
+                  [[ball]]\nposition = [390.0, 180.0]\nsize = \"small\"\nvelocity = [200.0, 0.0]\n\
+                  [[ring]]\nposition = [320.0, 180.0]\nsize = \"large\"\nrules = []\n";
+    let mut sim = Simulation::new(&Scene::from_toml(source).unwrap(), 1);
+    sim.step();
+    let removed = sim.balls()[0].position();
+    assert!(sim.remove_ball(0));
+    assert_eq!(sim.ball_at(removed), None);
+
+    // The new one heads for the top.
+    let mut ball = Ball::new_with_size([320.0, 180.0], BallSize::Small);
+    ball.set_velocity(Vector::new(0.0, -200.0));
+    assert_eq!(sim.add_ball(ball), Some(2));
+    sim.step();
+    assert_eq!(sim.ball_at(sim.balls()[2].position()), Some(2));
+    assert_eq!(sim.ball_at(sim.balls()[1].position()), Some(1));
+
+    let mut hits = [0; 3];
+    // Not long enough for either to come back to the opposite wall.
+    for _ in 0..60 {
+        sim.step();
+        for collision in sim.take_collisions() {
+            let ball = match (collision.first, collision.second) {
+                (Entity::Ball(ball), Entity::Ring(_)) | (Entity::Ring(_), Entity::Ball(ball)) => {
+                    ball
+                }
+                _ => continue,
+            };
+            let point = collision.impact.point;
+            match ball {
+                1 => assert!(point.x > 440.0, "ball 1 hit at {point}"),
+                2 => assert!(point.y < 60.0, "ball 2 hit at {point}"),
+                _ => panic!("removed ball {ball} collided"),
+            }
+            hits[ball] += 1;
+        }
+    }
+    assert!(hits[1] > 0 && hits[2] > 0);
+}
+
+#[test]
+fn voices_must_be_playable() {
+    let error = |voice: &str| {
+        Scene::from_toml(&format!("[voice.ball]\n{voice}\n"))
+            .err()
+            .map(|err| err.to_string())
+    };
+    assert_eq!(error("tone = { kind = \"pluck\" }"), None);
+    assert_eq!(
+        error("tone = { kind = \"pluck\" }\nfrequency = 0.0").as_deref(),
+        Some("invalid scene <inline>: voice.ball: frequency must be greater than 0")
+    );
+    assert!(error("tone = { kind = \"pluck\", damping = 1.5 }")
+        .unwrap()
+        .contains("voice.ball: pluck damping"));
+    assert!(
+        error("tone = { kind = \"sine\" }\nenvelope = { release = -0.1 }")
+            .unwrap()
+            .contains("envelope times must not be negative")
+    );
+    assert!(error("tone = { kind = \"sine\" }\ngain = -1.0")
+        .unwrap()
+        .contains("gain must not be negative"));
+    for (tone, problem) in [
+        (
+            "ratio = nan, index = 1.0",
+            "fm ratio must be greater than 0",
+        ),
+        (
+            "ratio = -2.0, index = 1.0",
+            "fm ratio must be greater than 0",
+        ),
+        ("ratio = 2.0, index = inf", "fm index must not be negative"),
+        ("ratio = 2.0, index = -1.0", "fm index must not be negative"),
+    ] {
+        let voice = format!("tone = {{ kind = \"fm\", {tone} }}");
+        assert!(error(&voice).unwrap().contains(problem), "{voice}");
+    }
+}
+
+#[test]
+fn voices_render_their_envelope_at_their_pitch() {
+    const RATE: u32 = 1000;
+    let mut voice = Voice::new(Tone::Square);
+    voice.envelope = Envelope {
+        attack: 0.1,
+        decay: 0.1,
+        sustain: 0.5,
+        release: 0.2,
+    };
+    voice.length = 0.4;
+    voice.gain = 1.0;
+    let samples = voice.render(1.0, RATE);
+    assert_eq!(samples.len(), 600);
+    let level = |seconds: f32| samples[(seconds * RATE as f32) as usize].abs();
+    assert!((level(0.05) - 0.5).abs() < 1e-3);
+    assert!((level(0.1) - 1.0).abs() < 1e-3);
+    assert!((level(0.3) - 0.5).abs() < 1e-3);
+    assert!((level(0.5) - 0.25).abs() < 1e-3);
+    assert!(samples.last().unwrap().abs() < 0.01);
+
+    // A freshly plucked string nearly repeats itself every period, so its
+    // samples come closest to the ones a period later.
+    let mut pluck = Voice::new(Tone::Pluck { damping: 0.0 });
+    pluck.frequency = 441.0;
+    pluck.envelope = Envelope {
+        attack: 0.0,
+        decay: 0.0,
+        sustain: 1.0,
+        release: 0.0,
+    };
+    let samples = pluck.render(2.0, 44100);
+    let distance = |lag: usize| -> f32 {
+        samples[..500]
+            .iter()
+            .zip(&samples[lag..])
+            .map(|(a, b)| (a - b).powi(2))
+            .sum()
+    };
+    let period = (10..75).min_by(|&a, &b| distance(a).total_cmp(&distance(b)));
+    assert!(period.is_some_and(|period| (50..=51).contains(&period)));
+}
+
+#[test]
+fn bounces_pan_to_where_they_hit() {
+    let source = |x: f32| {
+        format!(
+            "[[ball]]\nposition = [{x}, 180.0]\nsize = \"small\"\nvelocity = [300.0, 0.0]\n\
+             [[ring]]\nposition = [{x}, 180.0]\nsize = \"medium\"\nrules = []\n"
+        )
+    };
+    for x in [120.0, 320.0, 520.0] {
+        let (mut sim, _) = run(&source(x), 3.0);
+        let collisions = sim.take_collisions();
+        let triggers = sim.take_sound_triggers();
+        assert!(!triggers.is_empty());
+        for trigger in triggers {
+            let collision = collisions
+                .iter()
+                .find(|collision| collision.time == trigger.time)
+                .unwrap();
+            let pan = collision.impact.point.x / 640.0 * 2.0 - 1.0;
+            assert!((trigger.pan - pan).abs() < 1e-4);
+            assert!((trigger.pan - (x / 320.0 - 1.0)).abs() < 0.35);
+        }
+    }
+}
+
+#[test]
+fn harder_bounces_sound_louder() {
+    let first_volume = |speed: f32| {
+        let (mut sim, _) = run(
+            &ball_in_ring(
+                &format!("velocity = [0.0, {speed}]\nrules = []"),
+                "rules = []",
+            ),
+            2.0,
+        );
+        sim.take_sound_triggers()[0].volume
+    };
+    let volumes = [50.0, 200.0, 800.0].map(first_volume);
+    assert!(volumes[0] < volumes[1] && volumes[1] < volumes[2]);
+    assert!(volumes.iter().all(|&volume| (0.15..=3.0).contains(&volume)));
+}
+
+#[test]
+fn mixdowns_pan_with_equal_loudness() {
+    let path = std::env::temp_dir().join("collide-and-sound-pan-test.wav");
+    let mut mixdown = Mixdown::new(100);
+    let sound = Samples::from_mono(vec![0.5; 10], 100);
+    for (second, pan) in [-1.0, 0.0, 1.0].into_iter().enumerate() {
+        mixdown.add(&sound, second as f32, 1.0, 100.0, pan, None);
+    }
+    mixdown.write(&path).unwrap();
+    let samples: Vec<f32> = hound::WavReader::open(&path)
+        .unwrap()
+        .samples::<i16>()
+        .map(|sample| sample.unwrap() as f32 / i16::MAX as f32)
+        .collect();
+    std::fs::remove_file(&path).unwrap();
+    let frame = |second: usize| (samples[second * 200], samples[second * 200 + 1]);
+    let close = |(left, right): (f32, f32), expected: (f32, f32)| {
+        (left - expected.0).abs() < 1e-3 && (right - expected.1).abs() < 1e-3
+    };
+    assert!(close(frame(0), (0.5 * SQRT_2, 0.0)));
+    assert!(close(frame(1), (0.5, 0.5)));
+    assert!(close(frame(2), (0.0, 0.5 * SQRT_2)));
+}
+
+#[test]
+fn wav_mixdowns_give_way_by_polyphony() {
+    let scene = Scene::load("scenes/synth.toml").unwrap();
+    let sim = Simulation::new(&scene, 1);
+    let trigger = |time, volume| SoundTrigger {
+        time,
+        sound: SoundType::Ring,
+        pitch: 1.0,
+        volume,
+        pan: 0.0,
+        duration: None,
+    };
+    let (first, second) = (trigger(0.0, 100.0), trigger(0.2, 50.0));
+    let path = std::env::temp_dir().join("collide-and-sound-polyphony-test.wav");
+    let mix = |triggers: &[SoundTrigger], polyphony: &Polyphony| {
+        let paths = AssetPaths::new(Vec::new());
+        render_wav(
+            triggers,
+            sim.voices(),
+            sim.sound_bank(),
+            polyphony,
+            &paths,
+            1.0,
+            &path,
+        )
+        .unwrap();
+        let samples: Vec<i16> = hound::WavReader::open(&path)
+            .unwrap()
+            .samples::<i16>()
+            .map(Result::unwrap)
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        samples
+    };
+    let (first_alone, second_alone) = (
+        mix(&[first], sim.polyphony()),
+        mix(&[second], sim.polyphony()),
+    );
+    // Stereo frames before the second sound starts, and after the first
+    // has faded out for it.
+    let (before, after) = (..2 * 8800, 2 * 9100..);
+
+    let both = mix(&[first, second], sim.polyphony());
+    assert_ne!(both[after.clone()], second_alone[after.clone()]);
+
+    let mut one_voice = sim.polyphony().clone();
+    one_voice.max_voices = 1;
+    let stolen = mix(&[first, second], &one_voice);
+    assert_eq!(stolen[before], first_alone[before]);
+    assert_eq!(stolen[after.clone()], second_alone[after]);
+
+    one_voice.steal = StealPolicy::Quietest;
+    assert_eq!(mix(&[first, second], &one_voice), first_alone);
+}
+
+#[test]
+fn fully_voiced_scenes_preload_without_samples() {
+    let sampled = |path| Simulation::new(&Scene::load(path).unwrap(), 1).sampled_sounds();
+    assert!(sampled("scenes/synth.toml").is_empty());
+    assert_eq!(sampled("scenes/default.toml"), [SoundType::Bounce]);
+    assert!(sampled("scenes/concentric.toml").contains(&SoundType::Shatter));
+
+    #[cfg(feature = "sfml")]
+    {
+        use collide_and_sound::{assets::Assets, sounds::SoundList};
+
+        let sim = Simulation::new(&Scene::load("scenes/synth.toml").unwrap(), 1);
+        let assets = Assets::new(AssetPaths::new(Vec::new()));
+        let mut soundlist = SoundList::new(&assets);
+        soundlist.set_voices(*sim.voices());
+        assert!(soundlist.preload(&sim.sampled_sounds()).is_ok());
+        assert!(soundlist.preload(&[SoundType::Bounce]).is_err());
+    }
+}
+
+#[test]
+fn recordings_save_one_png_per_frame() {
+    let dir = std::env::temp_dir().join("collide-and-sound-record-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let source = "[window]\nwidth = 80\nheight = 60\n\
+                  [[ball]]\nposition = [40.0, 30.0]\nradius = 5.0\n\
+                  [[ring]]\nposition = [40.0, 30.0]\nradius = 25.0\n";
+    let mut sim = Simulation::new(&Scene::from_toml(source).unwrap(), 1);
+    let report = sim
+        .run_recording(RunLimit::Seconds(0.5), 10.0, &dir)
+        .unwrap();
+    assert!((report.simulated_seconds - 0.5).abs() < sim.timestep());
+
+    let mut frames: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    frames.sort();
+    let names: Vec<_> = frames
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(
+        names,
+        (0..5)
+            .map(|frame| format!("frame_{frame:06}.png"))
+            .collect::<Vec<_>>()
+    );
+    for path in &frames {
+        let decoder = png::Decoder::new(std::fs::File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (80, 60));
+        let drawn = pixels[..info.buffer_size()]
+            .chunks_exact(4)
+            .filter(|pixel| pixel[..3] != [0, 0, 0])
+            .count();
+        assert!(
+            drawn > 100,
+            "{} has only {drawn} pixels drawn",
+            path.display()
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}